@@ -0,0 +1,161 @@
+//! The derive macros behind `#[derive(sockit::Message)]` and `#[derive(sockit::MaxEncodedSize)]`
+//!
+//! See the documentation of the traits in sockit for how to use them. This crate is re-exported
+//! by sockit with its `derive` feature and isn't meant to be used directly.
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{parse_macro_input, parse_quote, Data, DeriveInput, Fields, LitInt};
+
+/// Implement `sockit::Message` for a struct or enum
+///
+/// The id defaults to a hash of the type's name and can be set with `#[message(id = 7)]`. The
+/// maximum encoded size defaults to the type's `MaxEncodedSize` if it implements it, and can be
+/// declared with `#[message(max_size = 512)]` as long as that isn't below it.
+#[proc_macro_derive(Message, attributes(message))]
+pub fn derive_message(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand_message(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Implement `sockit::MaxEncodedSize` for a struct or enum by adding up the sizes of its fields
+#[proc_macro_derive(MaxEncodedSize)]
+pub fn derive_max_encoded_size(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand_max_encoded_size(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+fn expand_max_encoded_size(mut input: DeriveInput) -> syn::Result<TokenStream2> {
+    let size = match &input.data {
+        Data::Struct(data) => sum_of(&data.fields),
+        Data::Enum(data) => {
+            // bincode encodes the variant as a u32 in front of the largest variant's fields
+            let variants = data.variants.iter().map(|variant| sum_of(&variant.fields));
+            quote! {{
+                let mut max = 0usize;
+                #(
+                    let size = #variants;
+                    if size > max {
+                        max = size;
+                    }
+                )*
+                max.saturating_add(4)
+            }}
+        }
+        Data::Union(data) => {
+            return Err(syn::Error::new_spanned(
+                data.union_token,
+                "unions have no bounded encoded size",
+            ))
+        }
+    };
+
+    if !input.generics.params.is_empty() {
+        let types: Vec<_> = match &input.data {
+            Data::Struct(data) => data.fields.iter().map(|field| field.ty.clone()).collect(),
+            Data::Enum(data) => data
+                .variants
+                .iter()
+                .flat_map(|variant| variant.fields.iter().map(|field| field.ty.clone()))
+                .collect(),
+            Data::Union(_) => unreachable!(),
+        };
+        let where_clause = input.generics.make_where_clause();
+        for ty in types {
+            where_clause
+                .predicates
+                .push(parse_quote!(#ty: ::sockit::MaxEncodedSize));
+        }
+    }
+
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::sockit::MaxEncodedSize for #name #ty_generics #where_clause {
+            const MAX_ENCODED_SIZE: usize = #size;
+        }
+    })
+}
+
+/// An expression adding up the sizes of some fields
+fn sum_of(fields: &Fields) -> TokenStream2 {
+    let types = fields.iter().map(|field| &field.ty);
+    quote!(0usize #(.saturating_add(<#types as ::sockit::MaxEncodedSize>::MAX_ENCODED_SIZE))*)
+}
+
+fn expand_message(input: DeriveInput) -> syn::Result<TokenStream2> {
+    if !input.generics.params.is_empty() {
+        return Err(syn::Error::new_spanned(
+            &input.generics,
+            "generic message types aren't supported",
+        ));
+    }
+    if let Data::Union(data) = &input.data {
+        return Err(syn::Error::new_spanned(
+            data.union_token,
+            "unions can't be messages",
+        ));
+    }
+
+    let mut id = None;
+    let mut max_size = None;
+    for attr in input
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("message"))
+    {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("id") {
+                id = Some(meta.value()?.parse::<LitInt>()?.base10_parse::<u32>()?);
+                Ok(())
+            } else if meta.path.is_ident("max_size") {
+                max_size = Some(meta.value()?.parse::<LitInt>()?.base10_parse::<usize>()?);
+                Ok(())
+            } else {
+                Err(meta.error("expected `id` or `max_size`"))
+            }
+        })?;
+    }
+
+    let name = &input.ident;
+    let id = id.unwrap_or_else(|| fnv1a_32(name.to_string().as_bytes()));
+    // Inherent constants shadow trait ones, so the fallback only applies without a bound
+    let bound = quote! {{
+        use ::sockit::__private::Unbounded as _;
+        ::sockit::__private::MaxSizeOf::<#name>::MAX_SIZE
+    }};
+    let (max_size, check) = match max_size {
+        Some(size) => (
+            quote!(::core::option::Option::Some(#size)),
+            quote! {
+                const _: () = {
+                    if let ::core::option::Option::Some(bound) = #bound {
+                        ::core::assert!(
+                            bound <= #size,
+                            "the declared max_size is below the type's MaxEncodedSize"
+                        );
+                    }
+                };
+            },
+        ),
+        None => (bound, TokenStream2::new()),
+    };
+
+    Ok(quote! {
+        impl ::sockit::Message for #name {
+            const ID: u32 = #id;
+            const MAX_SIZE: ::core::option::Option<usize> = #max_size;
+        }
+        #check
+    })
+}
+
+fn fnv1a_32(bytes: &[u8]) -> u32 {
+    bytes.iter().fold(0x811c_9dc5, |hash, &byte| {
+        (hash ^ byte as u32).wrapping_mul(0x0100_0193)
+    })
+}
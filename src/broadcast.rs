@@ -0,0 +1,45 @@
+use crate::{Codec, UdpSocket, UdpSocketError};
+use serde::Serialize;
+use std::net::{Ipv4Addr, SocketAddr};
+
+impl<C: Codec> UdpSocket<C> {
+    /// Allow or forbid writing to broadcast addresses by setting `SO_BROADCAST`
+    ///
+    /// Writing to a broadcast address while this is disabled returns
+    /// [`UdpSocketError::BroadcastDisabled`].
+    pub fn set_broadcast(&self, on: bool) -> Result<(), UdpSocketError> {
+        Ok(self.outbound.socket.set_broadcast(on)?)
+    }
+
+    /// Get whether writing to broadcast addresses is allowed
+    pub fn broadcast(&self) -> Result<bool, UdpSocketError> {
+        Ok(self.outbound.socket.broadcast()?)
+    }
+
+    /// Write a serializable value to every host on the local network at the given port
+    ///
+    /// The value is sent to the limited broadcast address `255.255.255.255`. Broadcast must be
+    /// enabled with [`set_broadcast`](Self::set_broadcast) first.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use sockit::UdpSocket;
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    ///   let mut socket = UdpSocket::bind("0.0.0.0:0").await?;
+    ///   socket.set_broadcast(true)?;
+    ///   socket.write_broadcast(&"Hello World!", 9090).await?;
+    ///   Ok(())
+    /// }
+    /// ```
+    pub async fn write_broadcast<T: Serialize>(
+        &mut self,
+        value: &T,
+        port: u16,
+    ) -> Result<(), UdpSocketError> {
+        let send_to = SocketAddr::from((Ipv4Addr::BROADCAST, port));
+        self.write(value, send_to).await
+    }
+}
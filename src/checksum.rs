@@ -0,0 +1,68 @@
+use crate::UdpSocketError;
+use std::net::SocketAddr;
+
+/// An integrity checksum appended to every datagram
+///
+/// UDP's own 16-bit checksum is weak and optional on IPv4, so corrupted datagrams can reach
+/// [`read`](crate::UdpSocket::read) and deserialize into wrong values. With a checksum enabled,
+/// corrupted datagrams are reported as [`UdpSocketError::ChecksumMismatch`] instead. Both peers
+/// must use the same checksum.
+///
+/// # Example
+///
+/// ```no_run
+/// use sockit::{Checksum, UdpSocket};
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+///   let mut socket = UdpSocket::bind("127.0.0.1:0").await?;
+///   socket.set_checksum(Checksum::Xxh3);
+///   Ok(())
+/// }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Checksum {
+    /// A 4-byte CRC-32 (IEEE)
+    Crc32,
+    /// An 8-byte XXH3 hash, which is faster on large datagrams and detects more errors
+    Xxh3,
+}
+
+impl Checksum {
+    /// The number of bytes the checksum adds to every datagram
+    pub fn size(self) -> usize {
+        match self {
+            Checksum::Crc32 => 4,
+            Checksum::Xxh3 => 8,
+        }
+    }
+
+    fn compute(self, bytes: &[u8]) -> u64 {
+        match self {
+            Checksum::Crc32 => crc32fast::hash(bytes) as u64,
+            Checksum::Xxh3 => xxhash_rust::xxh3::xxh3_64(bytes),
+        }
+    }
+
+    /// Append the checksum of a datagram to it
+    pub(crate) fn append(self, datagram: &mut Vec<u8>) {
+        let checksum = self.compute(datagram).to_be_bytes();
+        datagram.extend_from_slice(&checksum[8 - self.size()..]);
+    }
+
+    /// Verify the checksum at the end of a datagram received from `from`, returning the length
+    /// of the datagram without it
+    pub(crate) fn verify(self, from: SocketAddr, datagram: &[u8]) -> Result<usize, UdpSocketError> {
+        let mismatch = UdpSocketError::ChecksumMismatch { from };
+        let len = match datagram.len().checked_sub(self.size()) {
+            Some(len) => len,
+            None => return Err(mismatch),
+        };
+        let mut expected = [0; 8];
+        expected[8 - self.size()..].copy_from_slice(&datagram[len..]);
+        if self.compute(&datagram[..len]) != u64::from_be_bytes(expected) {
+            return Err(mismatch);
+        }
+        Ok(len)
+    }
+}
@@ -0,0 +1,90 @@
+use crate::UdpSocketError;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+/// A serialization format used by a [`UdpSocket`](crate::UdpSocket) to turn values into datagrams
+///
+/// Implement this trait to plug a custom format into a socket. Errors produced by third-party
+/// formats can be reported through [`UdpSocketError::CodecError`].
+///
+/// # Example
+///
+/// ```no_run
+/// use serde::{de::DeserializeOwned, Serialize};
+/// use sockit::{Codec, UdpSocket, UdpSocketError};
+///
+/// #[derive(Clone, Copy, Default)]
+/// struct MyCodec;
+///
+/// impl Codec for MyCodec {
+///     fn encode<T: Serialize>(&self, value: &T, buf: &mut Vec<u8>) -> Result<(), UdpSocketError> {
+///         bincode::serialize_into(buf, value).map_err(UdpSocketError::from)
+///     }
+///
+///     fn decode<T: DeserializeOwned>(&self, bytes: &[u8]) -> Result<T, UdpSocketError> {
+///         bincode::deserialize(bytes).map_err(UdpSocketError::from)
+///     }
+/// }
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+///   let socket = UdpSocket::bind("127.0.0.1:0").await?.with_codec(MyCodec);
+///   Ok(())
+/// }
+/// ```
+pub trait Codec {
+    /// Serialize a value, appending the encoded bytes to `buf`
+    fn encode<T: Serialize>(&self, value: &T, buf: &mut Vec<u8>) -> Result<(), UdpSocketError>;
+
+    /// Deserialize a value from the encoded bytes of a single message
+    fn decode<T: DeserializeOwned>(&self, bytes: &[u8]) -> Result<T, UdpSocketError>;
+
+    /// Whether values encode to at most their [`MaxEncodedSize`](crate::MaxEncodedSize), which
+    /// counts the bytes written by bincode
+    ///
+    /// The [maximum sizes](crate::Message::MAX_SIZE) declared by messages are only enforced for
+    /// codecs that return `true`. Defaults to `false`.
+    fn honors_max_encoded_size(&self) -> bool {
+        false
+    }
+}
+
+/// A [`Codec`] that uses [bincode](https://docs.rs/bincode) with its default configuration
+///
+/// This is the codec used by a [`UdpSocket`](crate::UdpSocket) unless another one is chosen.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BincodeCodec;
+
+impl Codec for BincodeCodec {
+    fn encode<T: Serialize>(&self, value: &T, buf: &mut Vec<u8>) -> Result<(), UdpSocketError> {
+        bincode::serialize_into(buf, value)?;
+        Ok(())
+    }
+
+    fn decode<T: DeserializeOwned>(&self, bytes: &[u8]) -> Result<T, UdpSocketError> {
+        Ok(bincode::deserialize(bytes)?)
+    }
+
+    fn honors_max_encoded_size(&self) -> bool {
+        true
+    }
+}
+
+/// A human-readable [`Codec`] that uses [serde_json](https://docs.rs/serde_json)
+///
+/// Requires the `json` feature.
+#[cfg(feature = "json")]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct JsonCodec;
+
+#[cfg(feature = "json")]
+impl Codec for JsonCodec {
+    fn encode<T: Serialize>(&self, value: &T, buf: &mut Vec<u8>) -> Result<(), UdpSocketError> {
+        serde_json::to_writer(buf, value)?;
+        Ok(())
+    }
+
+    fn decode<T: DeserializeOwned>(&self, bytes: &[u8]) -> Result<T, UdpSocketError> {
+        Ok(serde_json::from_slice(bytes)?)
+    }
+}
@@ -0,0 +1,151 @@
+// Without any algorithm compiled in, `Compression` can't be constructed and most of this module
+// is unreachable
+#![cfg_attr(
+    not(any(feature = "lz4", feature = "zstd")),
+    allow(unused_variables, unreachable_code)
+)]
+
+use crate::UdpSocketError;
+use std::net::SocketAddr;
+
+const UNCOMPRESSED: u8 = 0;
+#[cfg(feature = "lz4")]
+const LZ4: u8 = 1;
+#[cfg(feature = "zstd")]
+const ZSTD: u8 = 2;
+
+/// Transparent compression of the values written by a [`UdpSocket`](crate::UdpSocket)
+///
+/// When compression is enabled, every message starts with a one-byte flag naming the algorithm
+/// that compressed it, so the receiver decompresses it automatically. Messages that don't get
+/// smaller are sent uncompressed. Both peers must enable compression, but they don't need to
+/// choose the same algorithm as long as both are compiled in.
+///
+/// The algorithms are available behind the `lz4` and `zstd` features.
+///
+/// # Example
+///
+/// ```no_run
+/// # #[cfg(feature = "lz4")]
+/// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+/// use sockit::{Compression, UdpSocket};
+///
+/// let mut socket = UdpSocket::bind("127.0.0.1:0").await?;
+/// socket.set_compression(Compression::lz4().max_decompressed_size(64 * 1024));
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Compression {
+    algorithm: Algorithm,
+    max_decompressed_size: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Algorithm {
+    #[cfg(feature = "lz4")]
+    Lz4,
+    #[cfg(feature = "zstd")]
+    Zstd(i32),
+}
+
+impl Compression {
+    /// The default limit on the size of a decompressed message, 1 MiB
+    pub const DEFAULT_MAX_DECOMPRESSED_SIZE: usize = 1 << 20;
+
+    /// Compress messages with LZ4, which is very fast and works well on repetitive data
+    ///
+    /// Requires the `lz4` feature.
+    #[cfg(feature = "lz4")]
+    pub fn lz4() -> Self {
+        Self::new(Algorithm::Lz4)
+    }
+
+    /// Compress messages with Zstandard at the given level, trading speed for a better ratio
+    ///
+    /// Requires the `zstd` feature.
+    #[cfg(feature = "zstd")]
+    pub fn zstd(level: i32) -> Self {
+        Self::new(Algorithm::Zstd(level))
+    }
+
+    #[allow(dead_code)]
+    fn new(algorithm: Algorithm) -> Self {
+        Self {
+            algorithm,
+            max_decompressed_size: Self::DEFAULT_MAX_DECOMPRESSED_SIZE,
+        }
+    }
+
+    /// Set the largest size a received message may decompress to
+    ///
+    /// Messages that claim to be larger are rejected with
+    /// [`UdpSocketError::DecompressionLimitExceeded`] before they are decompressed, which
+    /// protects the receiver from decompression bombs.
+    pub fn max_decompressed_size(mut self, size: usize) -> Self {
+        self.max_decompressed_size = size;
+        self
+    }
+
+    /// Compress a message, prefixing it with the flag that tells the receiver how to decompress
+    pub(crate) fn compress(&self, frame: &[u8]) -> Result<Vec<u8>, UdpSocketError> {
+        let (flag, compressed): (u8, Vec<u8>) = match self.algorithm {
+            #[cfg(feature = "lz4")]
+            Algorithm::Lz4 => (LZ4, lz4_flex::block::compress_prepend_size(frame)),
+            #[cfg(feature = "zstd")]
+            Algorithm::Zstd(level) => (ZSTD, zstd::bulk::compress(frame, level)?),
+        };
+
+        let mut message = Vec::with_capacity(1 + frame.len().min(compressed.len()));
+        if compressed.len() < frame.len() {
+            message.push(flag);
+            message.extend_from_slice(&compressed);
+        } else {
+            message.push(UNCOMPRESSED);
+            message.extend_from_slice(frame);
+        }
+        Ok(message)
+    }
+
+    /// Decompress a message received from `from` according to its flag
+    pub(crate) fn decompress(
+        &self,
+        from: SocketAddr,
+        message: &[u8],
+    ) -> Result<Vec<u8>, UdpSocketError> {
+        let (&flag, body) = message
+            .split_first()
+            .ok_or(UdpSocketError::MalformedFrame { from })?;
+        let limit = self.max_decompressed_size;
+
+        match flag {
+            UNCOMPRESSED => Ok(body.to_vec()),
+            #[cfg(feature = "lz4")]
+            LZ4 => {
+                if body.len() < 4 {
+                    return Err(UdpSocketError::MalformedFrame { from });
+                }
+                let (size, compressed) = body.split_at(4);
+                let size = u32::from_le_bytes([size[0], size[1], size[2], size[3]]) as usize;
+                if size > limit {
+                    return Err(UdpSocketError::DecompressionLimitExceeded { from, limit });
+                }
+                lz4_flex::block::decompress(compressed, size)
+                    .map_err(|_| UdpSocketError::MalformedFrame { from })
+            }
+            #[cfg(feature = "zstd")]
+            ZSTD => {
+                match zstd::zstd_safe::get_frame_content_size(body) {
+                    Ok(Some(size)) if size <= limit as u64 => {}
+                    Ok(Some(_)) => {
+                        return Err(UdpSocketError::DecompressionLimitExceeded { from, limit })
+                    }
+                    _ => return Err(UdpSocketError::MalformedFrame { from }),
+                }
+                zstd::bulk::decompress(body, limit)
+                    .map_err(|_| UdpSocketError::MalformedFrame { from })
+            }
+            flag => Err(UdpSocketError::UnsupportedCompression { from, flag }),
+        }
+    }
+}
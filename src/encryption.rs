@@ -0,0 +1,228 @@
+use crate::UdpSocketError;
+use chacha20poly1305::aead::AeadInPlace;
+use chacha20poly1305::{KeyInit, Tag, XChaCha20Poly1305, XNonce};
+use std::fmt;
+use std::net::SocketAddr;
+use std::ops::Range;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+const SENDER_LEN: usize = 16;
+const NONCE_LEN: usize = SENDER_LEN + 8;
+const TAG_LEN: usize = 16;
+
+/// The number of bytes encryption adds to every datagram
+pub(crate) const OVERHEAD: usize = NONCE_LEN + TAG_LEN;
+
+/// What to do with a received datagram that fails a check
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RejectPolicy {
+    /// Return an error from `read`
+    Error,
+    /// Silently drop the datagram and wait for the next one
+    Drop,
+}
+
+impl Default for RejectPolicy {
+    fn default() -> Self {
+        Self::Error
+    }
+}
+
+/// Authenticated encryption of every datagram with a pre-shared key
+///
+/// Datagrams are encrypted with XChaCha20-Poly1305. Each one carries a unique 24-byte nonce,
+/// made of a 128-bit per-socket prefix drawn from the operating system's random number generator
+/// and a counter, and a 16-byte authentication tag. The prefix is large enough that any number of
+/// sockets can share a key without reusing a nonce. Datagrams that were forged, tampered with or
+/// encrypted with a different key fail authentication and are handled according to the
+/// [`RejectPolicy`].
+///
+/// The counter in the nonce also protects against replayed datagrams. The receiver remembers
+/// which of the last [`replay_window`](Self::replay_window) counters it received from each
+/// sender and rejects repeats as well as datagrams that are older than the window. Senders are
+/// told apart by the prefix of their nonces rather than their address, so a datagram replayed
+/// from another address is rejected too. Windows are kept for the 4096 most recently active
+/// senders.
+///
+/// # Example
+///
+/// ```no_run
+/// use sockit::{Encryption, RejectPolicy, UdpSocket};
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+///   let key = [7; 32];
+///   let mut socket = UdpSocket::bind("127.0.0.1:0").await?;
+///   socket.set_encryption(
+///       Encryption::new(key)
+///           .on_failure(RejectPolicy::Drop)
+///           .replay_window(4096),
+///   );
+///   Ok(())
+/// }
+/// ```
+#[derive(Clone)]
+pub struct Encryption {
+    cipher: XChaCha20Poly1305,
+    on_failure: RejectPolicy,
+    replay_window: usize,
+    on_replay: RejectPolicy,
+}
+
+/// A datagram that was decrypted in place
+pub(crate) struct Opened {
+    /// The random nonce prefix of the socket that sent the datagram
+    pub(crate) sender: u128,
+    /// The sender's count of datagrams encrypted before this one
+    pub(crate) counter: u64,
+    /// The range of the buffer holding the plaintext
+    pub(crate) plaintext: Range<usize>,
+}
+
+impl Encryption {
+    /// The default number of datagrams tracked by the replay window
+    pub const DEFAULT_REPLAY_WINDOW: usize = 1024;
+
+    /// The largest number of datagrams a replay window may track
+    pub const MAX_REPLAY_WINDOW: usize = 65536;
+
+    /// Encrypt datagrams with a 256-bit key shared by both peers
+    pub fn new(key: [u8; 32]) -> Self {
+        Self {
+            cipher: XChaCha20Poly1305::new(&key.into()),
+            on_failure: RejectPolicy::default(),
+            replay_window: Self::DEFAULT_REPLAY_WINDOW,
+            on_replay: RejectPolicy::default(),
+        }
+    }
+
+    /// Set what happens to datagrams that fail authentication
+    ///
+    /// By default, `read` returns [`UdpSocketError::AuthenticationFailed`].
+    pub fn on_failure(mut self, policy: RejectPolicy) -> Self {
+        self.on_failure = policy;
+        self
+    }
+
+    /// Get what happens to datagrams that fail authentication
+    pub fn failure_policy(&self) -> RejectPolicy {
+        self.on_failure
+    }
+
+    /// Set how many recent datagrams from each sender are checked for replays
+    ///
+    /// Datagrams may be reordered by at most this many positions before they are rejected as
+    /// too old. Every sender's window takes about `size / 8` bytes.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `size` is zero or larger than [`MAX_REPLAY_WINDOW`](Self::MAX_REPLAY_WINDOW).
+    pub fn replay_window(mut self, size: usize) -> Self {
+        assert_replay_window(size);
+        self.replay_window = size;
+        self
+    }
+
+    /// Set what happens to replayed datagrams
+    ///
+    /// By default, `read` returns [`UdpSocketError::ReplayDetected`].
+    pub fn on_replay(mut self, policy: RejectPolicy) -> Self {
+        self.on_replay = policy;
+        self
+    }
+
+    /// Get how many recent datagrams from each sender are checked for replays
+    pub fn replay_window_size(&self) -> usize {
+        self.replay_window
+    }
+
+    /// Get what happens to replayed datagrams
+    pub fn replay_policy(&self) -> RejectPolicy {
+        self.on_replay
+    }
+
+    /// Decrypt the datagram in `buffer` in place
+    pub(crate) fn open(
+        &self,
+        from: SocketAddr,
+        buffer: &mut [u8],
+    ) -> Result<Opened, UdpSocketError> {
+        let failed = UdpSocketError::AuthenticationFailed { from };
+        if buffer.len() < OVERHEAD {
+            return Err(failed);
+        }
+        let len = buffer.len();
+        let (nonce, rest) = buffer.split_at_mut(NONCE_LEN);
+        let (body, tag) = rest.split_at_mut(rest.len() - TAG_LEN);
+        self.cipher
+            .decrypt_in_place_detached(XNonce::from_slice(nonce), &[], body, Tag::from_slice(tag))
+            .map_err(|_| failed)?;
+
+        let (sender, counter) = nonce.split_at(SENDER_LEN);
+        Ok(Opened {
+            sender: u128::from_be_bytes(sender.try_into().unwrap()),
+            counter: u64::from_be_bytes(counter.try_into().unwrap()),
+            plaintext: NONCE_LEN..len - TAG_LEN,
+        })
+    }
+}
+
+impl fmt::Debug for Encryption {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Never print the key
+        f.debug_struct("Encryption")
+            .field("on_failure", &self.on_failure)
+            .field("replay_window", &self.replay_window)
+            .field("on_replay", &self.on_replay)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Encrypts outgoing datagrams, shared by all clones of a socket's sending side
+#[derive(Clone)]
+pub(crate) struct Sealer {
+    pub(crate) encryption: Encryption,
+    prefix: [u8; SENDER_LEN],
+    counter: Arc<AtomicU64>,
+}
+
+impl Sealer {
+    pub(crate) fn new(encryption: Encryption) -> Self {
+        let mut prefix = [0; SENDER_LEN];
+        getrandom::getrandom(&mut prefix)
+            .expect("the operating system's random number generator is unavailable");
+        Self {
+            encryption,
+            prefix,
+            counter: Arc::default(),
+        }
+    }
+
+    /// Encrypt a datagram under a fresh nonce
+    pub(crate) fn seal(&self, datagram: &[u8]) -> Vec<u8> {
+        let counter = self.counter.fetch_add(1, Ordering::Relaxed);
+        let mut sealed = Vec::with_capacity(datagram.len() + OVERHEAD);
+        sealed.extend_from_slice(&self.prefix);
+        sealed.extend_from_slice(&counter.to_be_bytes());
+        sealed.extend_from_slice(datagram);
+
+        let (nonce, body) = sealed.split_at_mut(NONCE_LEN);
+        let tag = self
+            .encryption
+            .cipher
+            .encrypt_in_place_detached(XNonce::from_slice(nonce), &[], body)
+            .expect("datagrams are far below the XChaCha20-Poly1305 length limit");
+        sealed.extend_from_slice(&tag);
+        sealed
+    }
+}
+
+/// Check that a replay window size is within the supported range
+pub(crate) fn assert_replay_window(size: usize) {
+    let max = Encryption::MAX_REPLAY_WINDOW;
+    assert!(
+        size > 0 && size <= max,
+        "replay window must track between 1 and {max} datagrams, got {size}"
+    );
+}
@@ -0,0 +1,105 @@
+use crate::UdpSocketError;
+use std::net::SocketAddr;
+use std::ops::RangeInclusive;
+
+/// The magic number at the start of every envelope
+const MAGIC: [u8; 4] = *b"SOCK";
+/// The version of the envelope format itself
+const WIRE_VERSION: u8 = 1;
+
+/// The number of bytes the envelope adds to every value
+pub(crate) const HEADER_LEN: usize = 4 + 1 + 2;
+
+/// A versioned header in front of every value, so that stray traffic and incompatible peers are
+/// rejected instead of producing garbage values
+///
+/// The header holds a magic number, the version of sockit's wire format and the version of the
+/// application protocol. [`read`](crate::UdpSocket::read) checks each of them in turn and reports
+/// the first that doesn't match with [`UdpSocketError::ForeignPacket`],
+/// [`UdpSocketError::UnsupportedWireVersion`] or [`UdpSocketError::ProtocolVersionMismatch`].
+///
+/// The envelope carries no type tag of its own. A tag derived from the type's name would change
+/// whenever the type is moved or renamed, or the compiler spells its name differently, so peers
+/// built from the same protocol could reject each other. To also check the type of every value,
+/// set a [`Registry`](crate::Registry) instead. The explicit tag it assigns to each type follows
+/// the envelope, and values of another type are reported with
+/// [`UdpSocketError::UnexpectedMessageType`].
+///
+/// # Example
+///
+/// ```no_run
+/// use sockit::{Envelope, UdpSocket};
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+///   let mut socket = UdpSocket::bind("127.0.0.1:0").await?;
+///   // Speak version 3 of our protocol, and still understand peers on version 2
+///   socket.set_envelope(Envelope::new(3).accept_versions(2..=3));
+///   Ok(())
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Envelope {
+    protocol_version: u16,
+    accepted: RangeInclusive<u16>,
+}
+
+impl Envelope {
+    /// Write values with this application protocol version, accepting only the same version
+    pub fn new(protocol_version: u16) -> Self {
+        Self {
+            protocol_version,
+            accepted: protocol_version..=protocol_version,
+        }
+    }
+
+    /// Set the application protocol versions accepted from peers
+    pub fn accept_versions(mut self, versions: RangeInclusive<u16>) -> Self {
+        self.accepted = versions;
+        self
+    }
+
+    /// Get the application protocol version values are written with
+    pub fn protocol_version(&self) -> u16 {
+        self.protocol_version
+    }
+
+    /// Get the application protocol versions accepted from peers
+    pub fn accepted_versions(&self) -> &RangeInclusive<u16> {
+        &self.accepted
+    }
+
+    /// Append the header for a value
+    pub(crate) fn wrap(&self, frame: &mut Vec<u8>) {
+        frame.extend_from_slice(&MAGIC);
+        frame.push(WIRE_VERSION);
+        frame.extend_from_slice(&self.protocol_version.to_be_bytes());
+    }
+
+    /// Check the header of a value received from `from`, returning the value's bytes
+    pub(crate) fn unwrap<'a>(
+        &self,
+        from: SocketAddr,
+        frame: &'a [u8],
+    ) -> Result<&'a [u8], UdpSocketError> {
+        if frame.len() < HEADER_LEN || frame[..4] != MAGIC {
+            return Err(UdpSocketError::ForeignPacket { from });
+        }
+        let (header, value) = frame.split_at(HEADER_LEN);
+        if header[4] != WIRE_VERSION {
+            return Err(UdpSocketError::UnsupportedWireVersion {
+                from,
+                version: header[4],
+            });
+        }
+        let version = u16::from_be_bytes([header[5], header[6]]);
+        if !self.accepted.contains(&version) {
+            return Err(UdpSocketError::ProtocolVersionMismatch {
+                from,
+                expected: self.accepted.clone(),
+                got: version,
+            });
+        }
+        Ok(value)
+    }
+}
@@ -0,0 +1,1334 @@
+//! Schema fingerprints that identify the serde shape of a type
+//!
+//! Values of types outside a [`Registry`](crate::Registry) are traced while they are encoded and
+//! decoded. The writer wraps the codec's serializer and the reader wraps its deserializer, and
+//! both record the same tokens for every serde call they pass on, which covers the names of
+//! types, fields and variants and the types of leaves, including those inside the options,
+//! sequences, maps and enum variants the value holds. Nothing that depends on the data itself is
+//! recorded, so two values only differ in their traces if their shapes differ.
+//!
+//! Types in a [`Registry`](crate::Registry) are known to both peers, so their fingerprint is
+//! traced once from their full schema instead, without any data: the trace descends into
+//! options, sequences and maps through a single placeholder element, and into every variant of
+//! an enum.
+use crate::{Codec, UdpSocketError};
+use serde::de::{self, DeserializeOwned, DeserializeSeed, IntoDeserializer, Visitor};
+use serde::ser::{self, Serialize};
+use serde::Deserialize;
+use std::any::TypeId;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt::{self, Write};
+use std::net::SocketAddr;
+
+/// The number of bytes the fingerprint adds to every value
+pub(crate) const LEN: usize = 8;
+
+/// How deep the schema trace may nest, which bounds recursive types
+const MAX_DEPTH: usize = 128;
+
+/// Check the fingerprint at the start of a value received from `from`, returning the value's
+/// bytes
+pub(crate) fn check(
+    from: SocketAddr,
+    expected: u64,
+    frame: &[u8],
+) -> Result<&[u8], UdpSocketError> {
+    if frame.len() < LEN {
+        return Err(UdpSocketError::TypeMismatch {
+            from,
+            expected,
+            got: 0,
+        });
+    }
+    let (fingerprint, value) = frame.split_at(LEN);
+    let got = u64::from_be_bytes(fingerprint.try_into().unwrap());
+    if got != expected {
+        return Err(UdpSocketError::TypeMismatch {
+            from,
+            expected,
+            got,
+        });
+    }
+    Ok(value)
+}
+
+/// Append a value encoded with `codec` to a frame, preceded by the fingerprint of its trace
+pub(crate) fn encode<C: Codec, T: Serialize + ?Sized>(
+    codec: &C,
+    value: &T,
+    frame: &mut Vec<u8>,
+) -> Result<(), UdpSocketError> {
+    let start = frame.len();
+    frame.extend_from_slice(&[0; LEN]);
+    let (encoded, fingerprint) = traced(|| codec.encode(&Tracing(value), frame));
+    encoded?;
+    frame[start..start + LEN].copy_from_slice(&fingerprint.to_be_bytes());
+    Ok(())
+}
+
+/// Decode a value received from `from` with `codec`, checking that the fingerprint in front of
+/// it matches the trace of decoding it
+///
+/// A value that fails to decode is reported as a mismatch if the trace up to the failure
+/// doesn't match, since it is most likely of another type.
+pub(crate) fn decode<C: Codec, T: DeserializeOwned>(
+    codec: &C,
+    from: SocketAddr,
+    frame: &[u8],
+) -> Result<T, UdpSocketError> {
+    let (got, value) = match frame.len() {
+        len if len < LEN => (0, &frame[len..]),
+        _ => {
+            let (fingerprint, value) = frame.split_at(LEN);
+            (u64::from_be_bytes(fingerprint.try_into().unwrap()), value)
+        }
+    };
+    let (decoded, expected) = traced(|| codec.decode::<Traced<T>>(value));
+    if got != expected {
+        return Err(UdpSocketError::TypeMismatch {
+            from,
+            expected,
+            got,
+        });
+    }
+    decoded.map(|Traced(value)| value)
+}
+
+/// The fingerprint of the full schema of the type `T`, including the contents of its options,
+/// sequences, maps and enum variants
+pub(crate) fn of_schema<T: DeserializeOwned + 'static>() -> u64 {
+    // Fingerprints that were already traced, kept per thread since tracing is deterministic
+    thread_local!(static CACHE: RefCell<HashMap<TypeId, u64>> = RefCell::default());
+    let type_id = TypeId::of::<T>();
+    if let Some(fingerprint) = CACHE.with(|cache| cache.borrow().get(&type_id).copied()) {
+        return fingerprint;
+    }
+
+    let mut tracer = Tracer::default();
+    // Every pass takes a different variant of each enum, until all variants were traced
+    let mut pass = 0;
+    let trace = loop {
+        // `Deserialize` impls that validate their input may reject the placeholder values
+        let _ = T::deserialize(&mut tracer);
+        pass += 1;
+        if pass >= tracer.variants {
+            break tracer.trace;
+        }
+        tracer.pass = pass;
+        tracer.depth = 0;
+        tracer.trace.push('|');
+    };
+    let mut fingerprint = Fnv::default();
+    let _ = fingerprint.write_str(&trace);
+    CACHE.with(|cache| cache.borrow_mut().insert(type_id, fingerprint.0));
+    fingerprint.0
+}
+
+/// A 64-bit FNV-1a hash, which is stable across platforms and releases
+struct Fnv(u64);
+
+impl Default for Fnv {
+    fn default() -> Self {
+        Self(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Write for Fnv {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for byte in s.bytes() {
+            self.0 = (self.0 ^ byte as u64).wrapping_mul(0x0000_0100_0000_01b3);
+        }
+        Ok(())
+    }
+}
+
+thread_local! {
+    /// The hash of the trace being recorded on this thread, if any
+    static TRACE: RefCell<Option<Fnv>> = const { RefCell::new(None) };
+}
+
+/// Run `f` while recording a trace of the values it encodes or decodes, returning its result
+/// and the fingerprint of the trace
+fn traced<R>(f: impl FnOnce() -> R) -> (R, u64) {
+    let outer = TRACE.with(|trace| trace.replace(Some(Fnv::default())));
+    let result = f();
+    let trace = TRACE.with(|trace| trace.replace(outer));
+    (result, trace.map_or(0, |trace| trace.0))
+}
+
+/// Add a token to the trace being recorded on this thread
+fn record(token: fmt::Arguments<'_>) {
+    TRACE.with(|trace| {
+        if let Some(trace) = trace.borrow_mut().as_mut() {
+            let _ = trace.write_fmt(token);
+        }
+    });
+}
+
+/// Record the name of a field or variant identified by `label` in a list of `names`
+fn record_name(names: &[&str], label: Label<'_>, suffix: &str) {
+    match label {
+        Label::Index(index) => match names.get(index as usize) {
+            Some(name) => record(format_args!("{name}{suffix}")),
+            None => record(format_args!("{index}{suffix}")),
+        },
+        Label::Name(name) => record(format_args!("{name}{suffix}")),
+    }
+}
+
+/// How a deserializer identified a field or variant
+#[derive(Clone, Copy)]
+enum Label<'a> {
+    Index(u64),
+    Name(&'a str),
+}
+
+/// A value that is traced while it is serialized
+struct Tracing<'a, T: ?Sized>(&'a T);
+
+impl<T: Serialize + ?Sized> Serialize for Tracing<'_, T> {
+    fn serialize<S: ser::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.0.serialize(TracingSerializer(serializer))
+    }
+}
+
+/// Records every call before passing it on to the serializer of a codec
+struct TracingSerializer<S>(S);
+
+macro_rules! serialize_leaves {
+    ($($method:ident($ty:ty) => $token:literal,)*) => {
+        $(
+            fn $method(self, value: $ty) -> Result<S::Ok, S::Error> {
+                record(format_args!($token));
+                self.0.$method(value)
+            }
+        )*
+    };
+}
+
+impl<S: ser::Serializer> ser::Serializer for TracingSerializer<S> {
+    type Ok = S::Ok;
+    type Error = S::Error;
+    type SerializeSeq = TracingCompound<S::SerializeSeq>;
+    type SerializeTuple = TracingCompound<S::SerializeTuple>;
+    type SerializeTupleStruct = TracingCompound<S::SerializeTupleStruct>;
+    type SerializeTupleVariant = TracingCompound<S::SerializeTupleVariant>;
+    type SerializeMap = TracingCompound<S::SerializeMap>;
+    type SerializeStruct = TracingCompound<S::SerializeStruct>;
+    type SerializeStructVariant = TracingCompound<S::SerializeStructVariant>;
+
+    serialize_leaves! {
+        serialize_bool(bool) => "bool,",
+        serialize_i8(i8) => "i8,",
+        serialize_i16(i16) => "i16,",
+        serialize_i32(i32) => "i32,",
+        serialize_i64(i64) => "i64,",
+        serialize_i128(i128) => "i128,",
+        serialize_u8(u8) => "u8,",
+        serialize_u16(u16) => "u16,",
+        serialize_u32(u32) => "u32,",
+        serialize_u64(u64) => "u64,",
+        serialize_u128(u128) => "u128,",
+        serialize_f32(f32) => "f32,",
+        serialize_f64(f64) => "f64,",
+        serialize_char(char) => "char,",
+        serialize_str(&str) => "str,",
+        serialize_bytes(&[u8]) => "bytes,",
+    }
+
+    fn serialize_none(self) -> Result<S::Ok, S::Error> {
+        record(format_args!("none,"));
+        self.0.serialize_none()
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<S::Ok, S::Error> {
+        record(format_args!("some("));
+        let ok = self.0.serialize_some(&Tracing(value))?;
+        record(format_args!("),"));
+        Ok(ok)
+    }
+
+    fn serialize_unit(self) -> Result<S::Ok, S::Error> {
+        record(format_args!("unit,"));
+        self.0.serialize_unit()
+    }
+
+    fn serialize_unit_struct(self, name: &'static str) -> Result<S::Ok, S::Error> {
+        record(format_args!("{name},"));
+        self.0.serialize_unit_struct(name)
+    }
+
+    fn serialize_unit_variant(
+        self,
+        name: &'static str,
+        index: u32,
+        variant: &'static str,
+    ) -> Result<S::Ok, S::Error> {
+        record(format_args!("{name}::{variant},"));
+        self.0.serialize_unit_variant(name, index, variant)
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        name: &'static str,
+        value: &T,
+    ) -> Result<S::Ok, S::Error> {
+        record(format_args!("{name}("));
+        let ok = self.0.serialize_newtype_struct(name, &Tracing(value))?;
+        record(format_args!("),"));
+        Ok(ok)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        name: &'static str,
+        index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<S::Ok, S::Error> {
+        record(format_args!("{name}::{variant}("));
+        let ok = self
+            .0
+            .serialize_newtype_variant(name, index, variant, &Tracing(value))?;
+        record(format_args!("),"));
+        Ok(ok)
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq, S::Error> {
+        record(format_args!("seq("));
+        TracingCompound::new(self.0.serialize_seq(len), "),")
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple, S::Error> {
+        record(format_args!("({len} "));
+        TracingCompound::new(self.0.serialize_tuple(len), "),")
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleStruct, S::Error> {
+        record(format_args!("{name}({len} "));
+        TracingCompound::new(self.0.serialize_tuple_struct(name, len), "),")
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        name: &'static str,
+        index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleVariant, S::Error> {
+        record(format_args!("{name}::{variant}("));
+        let compound = self.0.serialize_tuple_variant(name, index, variant, len);
+        TracingCompound::new(compound, "),")
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> Result<Self::SerializeMap, S::Error> {
+        record(format_args!("map("));
+        TracingCompound::new(self.0.serialize_map(len), "),")
+    }
+
+    fn serialize_struct(
+        self,
+        name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeStruct, S::Error> {
+        record(format_args!("{name}{{"));
+        TracingCompound::new(self.0.serialize_struct(name, len), "},")
+    }
+
+    fn serialize_struct_variant(
+        self,
+        name: &'static str,
+        index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeStructVariant, S::Error> {
+        record(format_args!("{name}::{variant}{{"));
+        let compound = self.0.serialize_struct_variant(name, index, variant, len);
+        TracingCompound::new(compound, "},")
+    }
+
+    fn is_human_readable(&self) -> bool {
+        self.0.is_human_readable()
+    }
+}
+
+/// The elements of a compound value being serialized, each of which is traced
+struct TracingCompound<S> {
+    inner: S,
+    close: &'static str,
+}
+
+impl<S> TracingCompound<S> {
+    fn new<E>(inner: Result<S, E>, close: &'static str) -> Result<Self, E> {
+        Ok(Self {
+            inner: inner?,
+            close,
+        })
+    }
+}
+
+macro_rules! tracing_compound {
+    ($($trait:ident::$method:ident),*) => {
+        $(
+            impl<S: ser::$trait> ser::$trait for TracingCompound<S> {
+                type Ok = S::Ok;
+                type Error = S::Error;
+
+                fn $method<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), S::Error> {
+                    self.inner.$method(&Tracing(value))
+                }
+
+                fn end(self) -> Result<S::Ok, S::Error> {
+                    let ok = self.inner.end()?;
+                    record(format_args!("{}", self.close));
+                    Ok(ok)
+                }
+            }
+        )*
+    };
+}
+
+tracing_compound!(
+    SerializeSeq::serialize_element,
+    SerializeTuple::serialize_element,
+    SerializeTupleStruct::serialize_field,
+    SerializeTupleVariant::serialize_field
+);
+
+impl<S: ser::SerializeMap> ser::SerializeMap for TracingCompound<S> {
+    type Ok = S::Ok;
+    type Error = S::Error;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), S::Error> {
+        self.inner.serialize_key(&Tracing(key))
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), S::Error> {
+        self.inner.serialize_value(&Tracing(value))
+    }
+
+    fn end(self) -> Result<S::Ok, S::Error> {
+        let ok = self.inner.end()?;
+        record(format_args!("{}", self.close));
+        Ok(ok)
+    }
+}
+
+macro_rules! tracing_fields {
+    ($($trait:ident),*) => {
+        $(
+            impl<S: ser::$trait> ser::$trait for TracingCompound<S> {
+                type Ok = S::Ok;
+                type Error = S::Error;
+
+                fn serialize_field<T: Serialize + ?Sized>(
+                    &mut self,
+                    key: &'static str,
+                    value: &T,
+                ) -> Result<(), S::Error> {
+                    record(format_args!("{key}:"));
+                    self.inner.serialize_field(key, &Tracing(value))
+                }
+
+                // Skipped fields aren't read either, so they leave no trace
+                fn skip_field(&mut self, key: &'static str) -> Result<(), S::Error> {
+                    self.inner.skip_field(key)
+                }
+
+                fn end(self) -> Result<S::Ok, S::Error> {
+                    let ok = self.inner.end()?;
+                    record(format_args!("{}", self.close));
+                    Ok(ok)
+                }
+            }
+        )*
+    };
+}
+
+tracing_fields!(SerializeStruct, SerializeStructVariant);
+
+/// A value that is traced while it is deserialized
+struct Traced<T>(T);
+
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for Traced<T> {
+    fn deserialize<D: de::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        T::deserialize(TracingDeserializer(deserializer)).map(Traced)
+    }
+}
+
+/// Records every call before passing it on to the deserializer of a codec, with the same
+/// tokens the [`TracingSerializer`] records for the matching calls
+struct TracingDeserializer<D>(D);
+
+macro_rules! trace_leaves {
+    ($($method:ident => $token:literal,)*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, D::Error> {
+                record(format_args!($token));
+                self.0.$method(visitor)
+            }
+        )*
+    };
+}
+
+impl<'de, D: de::Deserializer<'de>> de::Deserializer<'de> for TracingDeserializer<D> {
+    type Error = D::Error;
+
+    // Self-describing formats name fields and variants with strings, so unknown identifiers
+    // are recorded as those. Values that are only understood by inspecting them can't be traced
+    // like the writer did, so they never match.
+    trace_leaves! {
+        deserialize_bool => "bool,",
+        deserialize_i8 => "i8,",
+        deserialize_i16 => "i16,",
+        deserialize_i32 => "i32,",
+        deserialize_i64 => "i64,",
+        deserialize_i128 => "i128,",
+        deserialize_u8 => "u8,",
+        deserialize_u16 => "u16,",
+        deserialize_u32 => "u32,",
+        deserialize_u64 => "u64,",
+        deserialize_u128 => "u128,",
+        deserialize_f32 => "f32,",
+        deserialize_f64 => "f64,",
+        deserialize_char => "char,",
+        deserialize_str => "str,",
+        deserialize_string => "str,",
+        deserialize_bytes => "bytes,",
+        deserialize_byte_buf => "bytes,",
+        deserialize_unit => "unit,",
+        deserialize_identifier => "str,",
+        deserialize_any => "any,",
+        deserialize_ignored_any => "ignored,",
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, D::Error> {
+        self.0
+            .deserialize_option(TracingVisitor::new(visitor, Expect::Option))
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, D::Error> {
+        record(format_args!("{name},"));
+        self.0.deserialize_unit_struct(name, visitor)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, D::Error> {
+        record(format_args!("{name}("));
+        let visitor = TracingVisitor::new(visitor, Expect::Value);
+        let value = self.0.deserialize_newtype_struct(name, visitor)?;
+        record(format_args!("),"));
+        Ok(value)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, D::Error> {
+        record(format_args!("seq("));
+        let value = self
+            .0
+            .deserialize_seq(TracingVisitor::new(visitor, Expect::Value))?;
+        record(format_args!("),"));
+        Ok(value)
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(
+        self,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value, D::Error> {
+        record(format_args!("({len} "));
+        let visitor = TracingVisitor::new(visitor, Expect::Value);
+        let value = self.0.deserialize_tuple(len, visitor)?;
+        record(format_args!("),"));
+        Ok(value)
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value, D::Error> {
+        record(format_args!("{name}({len} "));
+        let visitor = TracingVisitor::new(visitor, Expect::Value);
+        let value = self.0.deserialize_tuple_struct(name, len, visitor)?;
+        record(format_args!("),"));
+        Ok(value)
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, D::Error> {
+        record(format_args!("map("));
+        let value = self
+            .0
+            .deserialize_map(TracingVisitor::new(visitor, Expect::Value))?;
+        record(format_args!("),"));
+        Ok(value)
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, D::Error> {
+        record(format_args!("{name}{{"));
+        let visitor = TracingVisitor::new(visitor, Expect::Fields(fields));
+        let value = self.0.deserialize_struct(name, fields, visitor)?;
+        record(format_args!("}},"));
+        Ok(value)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, D::Error> {
+        let visitor = TracingVisitor::new(visitor, Expect::Variant(name, variants));
+        self.0.deserialize_enum(name, variants, visitor)
+    }
+
+    fn is_human_readable(&self) -> bool {
+        self.0.is_human_readable()
+    }
+}
+
+/// What a [`TracingVisitor`] is visiting, which decides what it records
+#[derive(Clone, Copy)]
+enum Expect {
+    /// A value whose tokens were recorded when it was requested
+    Value,
+    /// An option, which is recorded once it is known whether it holds a value
+    Option,
+    /// A struct or struct variant with the given fields
+    Fields(&'static [&'static str]),
+    /// An enum with the given name and variants
+    Variant(&'static str, &'static [&'static str]),
+}
+
+/// Passes the values of a codec's deserializer on to the visitor of a type, tracing the values
+/// nested in them
+struct TracingVisitor<V> {
+    inner: V,
+    expect: Expect,
+}
+
+impl<V> TracingVisitor<V> {
+    fn new(inner: V, expect: Expect) -> Self {
+        Self { inner, expect }
+    }
+}
+
+macro_rules! forward_visits {
+    ($($method:ident($ty:ty),)*) => {
+        $(
+            fn $method<E: de::Error>(self, value: $ty) -> Result<V::Value, E> {
+                self.inner.$method(value)
+            }
+        )*
+    };
+}
+
+impl<'de, V: Visitor<'de>> Visitor<'de> for TracingVisitor<V> {
+    type Value = V::Value;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.inner.expecting(f)
+    }
+
+    forward_visits! {
+        visit_bool(bool),
+        visit_i8(i8),
+        visit_i16(i16),
+        visit_i32(i32),
+        visit_i64(i64),
+        visit_i128(i128),
+        visit_u8(u8),
+        visit_u16(u16),
+        visit_u32(u32),
+        visit_u64(u64),
+        visit_u128(u128),
+        visit_f32(f32),
+        visit_f64(f64),
+        visit_char(char),
+        visit_str(&str),
+        visit_borrowed_str(&'de str),
+        visit_string(String),
+        visit_bytes(&[u8]),
+        visit_borrowed_bytes(&'de [u8]),
+        visit_byte_buf(Vec<u8>),
+    }
+
+    fn visit_none<E: de::Error>(self) -> Result<V::Value, E> {
+        if let Expect::Option = self.expect {
+            record(format_args!("none,"));
+        }
+        self.inner.visit_none()
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<V::Value, E> {
+        if let Expect::Option = self.expect {
+            record(format_args!("none,"));
+        }
+        self.inner.visit_unit()
+    }
+
+    fn visit_some<D: de::Deserializer<'de>>(self, deserializer: D) -> Result<V::Value, D::Error> {
+        let option = matches!(self.expect, Expect::Option);
+        if option {
+            record(format_args!("some("));
+        }
+        let value = self.inner.visit_some(TracingDeserializer(deserializer))?;
+        if option {
+            record(format_args!("),"));
+        }
+        Ok(value)
+    }
+
+    fn visit_newtype_struct<D: de::Deserializer<'de>>(
+        self,
+        deserializer: D,
+    ) -> Result<V::Value, D::Error> {
+        self.inner
+            .visit_newtype_struct(TracingDeserializer(deserializer))
+    }
+
+    fn visit_seq<A: de::SeqAccess<'de>>(self, seq: A) -> Result<V::Value, A::Error> {
+        let fields = match self.expect {
+            Expect::Fields(fields) => Some(fields),
+            _ => None,
+        };
+        self.inner.visit_seq(TracingSeq {
+            inner: seq,
+            fields,
+            index: 0,
+        })
+    }
+
+    fn visit_map<A: de::MapAccess<'de>>(self, map: A) -> Result<V::Value, A::Error> {
+        let fields = match self.expect {
+            Expect::Fields(fields) => Some(fields),
+            _ => None,
+        };
+        self.inner.visit_map(TracingMap { inner: map, fields })
+    }
+
+    fn visit_enum<A: de::EnumAccess<'de>>(self, data: A) -> Result<V::Value, A::Error> {
+        let (name, variants) = match self.expect {
+            Expect::Variant(name, variants) => (name, variants),
+            _ => ("", &[][..]),
+        };
+        self.inner.visit_enum(TracingEnum {
+            inner: data,
+            name,
+            variants,
+        })
+    }
+}
+
+/// A seed that traces the value it deserializes
+struct TracingSeed<T>(T);
+
+impl<'de, T: DeserializeSeed<'de>> DeserializeSeed<'de> for TracingSeed<T> {
+    type Value = T::Value;
+
+    fn deserialize<D: de::Deserializer<'de>>(self, deserializer: D) -> Result<T::Value, D::Error> {
+        self.0.deserialize(TracingDeserializer(deserializer))
+    }
+}
+
+/// The elements of a sequence, tuple or struct being deserialized
+struct TracingSeq<A> {
+    inner: A,
+    /// The fields of a struct, which are recorded in order before each element
+    fields: Option<&'static [&'static str]>,
+    index: u64,
+}
+
+impl<'de, A: de::SeqAccess<'de>> de::SeqAccess<'de> for TracingSeq<A> {
+    type Error = A::Error;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, A::Error> {
+        if let Some(fields) = self.fields {
+            record_name(fields, Label::Index(self.index), ":");
+            self.index += 1;
+        }
+        self.inner.next_element_seed(TracingSeed(seed))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        self.inner.size_hint()
+    }
+}
+
+/// The entries of a map or struct being deserialized
+struct TracingMap<A> {
+    inner: A,
+    /// The fields of a struct, whose keys are recorded by name
+    fields: Option<&'static [&'static str]>,
+}
+
+impl<'de, A: de::MapAccess<'de>> de::MapAccess<'de> for TracingMap<A> {
+    type Error = A::Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, A::Error> {
+        match self.fields {
+            Some(fields) => self.inner.next_key_seed(IdentifierSeed {
+                inner: seed,
+                names: fields,
+                prefix: None,
+            }),
+            None => self.inner.next_key_seed(TracingSeed(seed)),
+        }
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, A::Error> {
+        self.inner.next_value_seed(TracingSeed(seed))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        self.inner.size_hint()
+    }
+}
+
+/// The variant of an enum being deserialized
+struct TracingEnum<A> {
+    inner: A,
+    name: &'static str,
+    variants: &'static [&'static str],
+}
+
+impl<'de, A: de::EnumAccess<'de>> de::EnumAccess<'de> for TracingEnum<A> {
+    type Error = A::Error;
+    type Variant = TracingVariant<A::Variant>;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(
+        self,
+        seed: V,
+    ) -> Result<(V::Value, Self::Variant), A::Error> {
+        let (variant, access) = self.inner.variant_seed(IdentifierSeed {
+            inner: seed,
+            names: self.variants,
+            prefix: Some(self.name),
+        })?;
+        Ok((variant, TracingVariant(access)))
+    }
+}
+
+/// The contents of an enum variant being deserialized
+struct TracingVariant<A>(A);
+
+impl<'de, A: de::VariantAccess<'de>> de::VariantAccess<'de> for TracingVariant<A> {
+    type Error = A::Error;
+
+    fn unit_variant(self) -> Result<(), A::Error> {
+        record(format_args!(","));
+        self.0.unit_variant()
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value, A::Error> {
+        record(format_args!("("));
+        let value = self.0.newtype_variant_seed(TracingSeed(seed))?;
+        record(format_args!("),"));
+        Ok(value)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, len: usize, visitor: V) -> Result<V::Value, A::Error> {
+        record(format_args!("("));
+        let value = self
+            .0
+            .tuple_variant(len, TracingVisitor::new(visitor, Expect::Value))?;
+        record(format_args!("),"));
+        Ok(value)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, A::Error> {
+        record(format_args!("{{"));
+        let visitor = TracingVisitor::new(visitor, Expect::Fields(fields));
+        let value = self.0.struct_variant(fields, visitor)?;
+        record(format_args!("}},"));
+        Ok(value)
+    }
+}
+
+/// A seed for the identifier of a struct field or enum variant, which records its name
+struct IdentifierSeed<T> {
+    inner: T,
+    names: &'static [&'static str],
+    /// The name of the enum, which is recorded in front of the variant, or `None` for a field
+    prefix: Option<&'static str>,
+}
+
+impl<'de, T: DeserializeSeed<'de>> DeserializeSeed<'de> for IdentifierSeed<T> {
+    type Value = T::Value;
+
+    fn deserialize<D: de::Deserializer<'de>>(self, deserializer: D) -> Result<T::Value, D::Error> {
+        self.inner.deserialize(IdentifierDeserializer {
+            inner: deserializer,
+            names: self.names,
+            prefix: self.prefix,
+        })
+    }
+}
+
+/// Passes an identifier on, capturing it as the deserializer hands it to the visitor
+struct IdentifierDeserializer<D> {
+    inner: D,
+    names: &'static [&'static str],
+    prefix: Option<&'static str>,
+}
+
+impl<D> IdentifierDeserializer<D> {
+    fn capture<V>(&self, visitor: V) -> Capture<V> {
+        Capture {
+            inner: visitor,
+            names: self.names,
+            prefix: self.prefix,
+        }
+    }
+}
+
+macro_rules! forward_identifier {
+    ($($method:ident($($arg:ident: $ty:ty),*),)*) => {
+        $(
+            fn $method<V: Visitor<'de>>(
+                self,
+                $($arg: $ty,)*
+                visitor: V,
+            ) -> Result<V::Value, D::Error> {
+                let visitor = self.capture(visitor);
+                self.inner.$method($($arg,)* visitor)
+            }
+        )*
+    };
+}
+
+impl<'de, D: de::Deserializer<'de>> de::Deserializer<'de> for IdentifierDeserializer<D> {
+    type Error = D::Error;
+
+    forward_identifier! {
+        deserialize_any(),
+        deserialize_bool(),
+        deserialize_i8(),
+        deserialize_i16(),
+        deserialize_i32(),
+        deserialize_i64(),
+        deserialize_i128(),
+        deserialize_u8(),
+        deserialize_u16(),
+        deserialize_u32(),
+        deserialize_u64(),
+        deserialize_u128(),
+        deserialize_f32(),
+        deserialize_f64(),
+        deserialize_char(),
+        deserialize_str(),
+        deserialize_string(),
+        deserialize_bytes(),
+        deserialize_byte_buf(),
+        deserialize_option(),
+        deserialize_unit(),
+        deserialize_unit_struct(name: &'static str),
+        deserialize_newtype_struct(name: &'static str),
+        deserialize_seq(),
+        deserialize_tuple(len: usize),
+        deserialize_tuple_struct(name: &'static str, len: usize),
+        deserialize_map(),
+        deserialize_struct(name: &'static str, fields: &'static [&'static str]),
+        deserialize_enum(name: &'static str, variants: &'static [&'static str]),
+        deserialize_identifier(),
+        deserialize_ignored_any(),
+    }
+
+    fn is_human_readable(&self) -> bool {
+        self.inner.is_human_readable()
+    }
+}
+
+/// Records the identifier of a field or variant by name, whether it was read as an index or a
+/// string
+struct Capture<V> {
+    inner: V,
+    names: &'static [&'static str],
+    prefix: Option<&'static str>,
+}
+
+impl<V> Capture<V> {
+    fn record(&self, label: Label<'_>) {
+        match self.prefix {
+            Some(name) => {
+                record(format_args!("{name}::"));
+                record_name(self.names, label, "");
+            }
+            None => record_name(self.names, label, ":"),
+        }
+    }
+}
+
+// Narrower integers, borrowed and owned strings and bytes are passed on to these by default
+impl<'de, V: Visitor<'de>> Visitor<'de> for Capture<V> {
+    type Value = V::Value;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.inner.expecting(f)
+    }
+
+    fn visit_u64<E: de::Error>(self, index: u64) -> Result<V::Value, E> {
+        self.record(Label::Index(index));
+        self.inner.visit_u64(index)
+    }
+
+    fn visit_str<E: de::Error>(self, name: &str) -> Result<V::Value, E> {
+        self.record(Label::Name(name));
+        self.inner.visit_str(name)
+    }
+
+    fn visit_bytes<E: de::Error>(self, name: &[u8]) -> Result<V::Value, E> {
+        self.record(Label::Name(&String::from_utf8_lossy(name)));
+        self.inner.visit_bytes(name)
+    }
+}
+
+#[derive(Debug)]
+struct Error(String);
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl de::Error for Error {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        Error(msg.to_string())
+    }
+}
+
+/// Records the full schema of a type by deserializing it from placeholder values
+#[derive(Default)]
+struct Tracer {
+    trace: String,
+    depth: usize,
+    /// The number of the current pass over the schema, which picks the variant of every enum
+    pass: usize,
+    /// The largest number of variants of any enum traced so far
+    variants: usize,
+}
+
+impl Tracer {
+    fn record(&mut self, token: fmt::Arguments<'_>) {
+        let _ = self.trace.write_fmt(token);
+    }
+
+    fn enter(&mut self) -> Result<(), Error> {
+        self.depth += 1;
+        if self.depth > MAX_DEPTH {
+            return Err(Error("the type is nested too deeply".to_string()));
+        }
+        Ok(())
+    }
+
+    fn leave(&mut self) {
+        self.depth -= 1;
+    }
+}
+
+macro_rules! deserialize_leaves {
+    ($($method:ident => $visit:ident($($value:expr)?), $token:literal,)*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+                self.record(format_args!($token));
+                visitor.$visit($($value)?)
+            }
+        )*
+    };
+}
+
+impl<'de> de::Deserializer<'de> for &mut Tracer {
+    type Error = Error;
+
+    // Numbers are 1 rather than 0 to satisfy types like `NonZeroU32`
+    deserialize_leaves! {
+        deserialize_bool => visit_bool(false), "bool,",
+        deserialize_i8 => visit_i8(1), "i8,",
+        deserialize_i16 => visit_i16(1), "i16,",
+        deserialize_i32 => visit_i32(1), "i32,",
+        deserialize_i64 => visit_i64(1), "i64,",
+        deserialize_i128 => visit_i128(1), "i128,",
+        deserialize_u8 => visit_u8(1), "u8,",
+        deserialize_u16 => visit_u16(1), "u16,",
+        deserialize_u32 => visit_u32(1), "u32,",
+        deserialize_u64 => visit_u64(1), "u64,",
+        deserialize_u128 => visit_u128(1), "u128,",
+        deserialize_f32 => visit_f32(1.0), "f32,",
+        deserialize_f64 => visit_f64(1.0), "f64,",
+        deserialize_char => visit_char('a'), "char,",
+        deserialize_str => visit_str(""), "str,",
+        deserialize_string => visit_string(String::new()), "str,",
+        deserialize_bytes => visit_bytes(&[]), "bytes,",
+        deserialize_byte_buf => visit_byte_buf(Vec::new()), "bytes,",
+        deserialize_unit => visit_unit(), "unit,",
+        deserialize_any => visit_unit(), "any,",
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.record(format_args!("option("));
+        self.enter()?;
+        let value = visitor.visit_some(&mut *self)?;
+        self.leave();
+        self.record(format_args!("),"));
+        Ok(value)
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.record(format_args!("{name},"));
+        visitor.visit_unit()
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.record(format_args!("{name}("));
+        self.enter()?;
+        let value = visitor.visit_newtype_struct(&mut *self)?;
+        self.leave();
+        self.record(format_args!("),"));
+        Ok(value)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.record(format_args!("seq("));
+        self.elements(visitor, 1, None, "),")
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(self, len: usize, visitor: V) -> Result<V::Value, Error> {
+        self.record(format_args!("({len} "));
+        self.elements(visitor, len, None, "),")
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.record(format_args!("{name}({len} "));
+        self.elements(visitor, len, None, "),")
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.record(format_args!("map("));
+        self.enter()?;
+        let value = visitor.visit_map(Entries {
+            tracer: &mut *self,
+            left: 1,
+        })?;
+        self.leave();
+        self.record(format_args!("),"));
+        Ok(value)
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.record(format_args!("{name}{{"));
+        self.elements(visitor, fields.len(), Some(fields), "},")
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        // An enum without variants can't be deserialized at all
+        let index = match self.pass.checked_rem(variants.len()) {
+            Some(index) => index,
+            None => {
+                self.record(format_args!("enum {name}{{}},"));
+                return Err(Error(format!("{name} has no variants")));
+            }
+        };
+        self.variants = self.variants.max(variants.len());
+        self.record(format_args!(
+            "enum {name}{} {}(",
+            variants.len(),
+            variants[index]
+        ));
+        self.enter()?;
+        let value = visitor.visit_enum(Variant {
+            tracer: &mut *self,
+            index: index as u32,
+        })?;
+        self.leave();
+        self.record(format_args!("),"));
+        Ok(value)
+    }
+
+    fn deserialize_identifier<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_u64(0)
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_unit()
+    }
+
+    fn is_human_readable(&self) -> bool {
+        false
+    }
+}
+
+impl Tracer {
+    /// Deserialize the elements of a tuple or struct, recording each of them
+    fn elements<'de, V: Visitor<'de>>(
+        &mut self,
+        visitor: V,
+        len: usize,
+        fields: Option<&'static [&'static str]>,
+        end: &'static str,
+    ) -> Result<V::Value, Error> {
+        self.enter()?;
+        let value = visitor.visit_seq(Elements {
+            tracer: self,
+            index: 0,
+            len,
+            fields,
+        })?;
+        self.leave();
+        self.record(format_args!("{end}"));
+        Ok(value)
+    }
+}
+
+struct Elements<'a> {
+    tracer: &'a mut Tracer,
+    index: usize,
+    len: usize,
+    fields: Option<&'static [&'static str]>,
+}
+
+impl<'de> de::SeqAccess<'de> for Elements<'_> {
+    type Error = Error;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, Error> {
+        if self.index == self.len {
+            return Ok(None);
+        }
+        if let Some(fields) = self.fields {
+            self.tracer.record(format_args!("{}:", fields[self.index]));
+        }
+        self.index += 1;
+        seed.deserialize(&mut *self.tracer).map(Some)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.len - self.index)
+    }
+}
+
+/// A map holding placeholder entries, whose keys and values are recorded
+struct Entries<'a> {
+    tracer: &'a mut Tracer,
+    left: usize,
+}
+
+impl<'de> de::MapAccess<'de> for Entries<'_> {
+    type Error = Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, Error> {
+        if self.left == 0 {
+            return Ok(None);
+        }
+        self.left -= 1;
+        seed.deserialize(&mut *self.tracer).map(Some)
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, Error> {
+        seed.deserialize(&mut *self.tracer)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.left)
+    }
+}
+
+/// The variant of an enum picked for a trace
+struct Variant<'a> {
+    tracer: &'a mut Tracer,
+    index: u32,
+}
+
+impl<'de, 'a> de::EnumAccess<'de> for Variant<'a> {
+    type Error = Error;
+    type Variant = &'a mut Tracer;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(
+        self,
+        seed: V,
+    ) -> Result<(V::Value, &'a mut Tracer), Error> {
+        let variant = seed.deserialize(self.index.into_deserializer())?;
+        Ok((variant, self.tracer))
+    }
+}
+
+impl<'de> de::VariantAccess<'de> for &mut Tracer {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value, Error> {
+        seed.deserialize(self)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, len: usize, visitor: V) -> Result<V::Value, Error> {
+        self.elements(visitor, len, None, "")
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.elements(visitor, fields.len(), Some(fields), "")
+    }
+}
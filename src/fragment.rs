@@ -0,0 +1,288 @@
+use crate::UdpSocketError;
+use std::collections::HashMap;
+use std::mem;
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+
+/// The number of bytes prepended to every datagram when fragmentation is enabled
+pub(crate) const HEADER_LEN: usize = 8;
+
+/// Configuration for splitting large values across several datagrams
+///
+/// When fragmentation is enabled on a [`UdpSocket`](crate::UdpSocket), every datagram carries a
+/// small header with a message id and the fragment's position, and [`read`](crate::UdpSocket::read)
+/// only returns once all fragments of a message have arrived. Both peers must enable it.
+///
+/// # Example
+///
+/// ```no_run
+/// use sockit::{Fragmentation, UdpSocket};
+/// use std::time::Duration;
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+///   let mut socket = UdpSocket::bind("127.0.0.1:0").await?;
+///   socket.set_fragmentation(
+///       Fragmentation::new()
+///           .reassembly_timeout(Duration::from_secs(2))
+///           .max_pending_bytes(1 << 20)
+///           .max_pending_messages(8)
+///           .max_pending_peers(16),
+///   );
+///   Ok(())
+/// }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Fragmentation {
+    reassembly_timeout: Duration,
+    max_pending_bytes: usize,
+    max_pending_messages: usize,
+    max_pending_peers: usize,
+}
+
+impl Fragmentation {
+    /// Create a configuration with a 5 second reassembly timeout and a 4 MiB memory cap for up to
+    /// 16 incomplete messages per peer from up to 64 peers at once
+    pub fn new() -> Self {
+        Self {
+            reassembly_timeout: Duration::from_secs(5),
+            max_pending_bytes: 4 << 20,
+            max_pending_messages: 16,
+            max_pending_peers: 64,
+        }
+    }
+
+    /// Set how long a partially received message is kept before its fragments are discarded
+    pub fn reassembly_timeout(mut self, timeout: Duration) -> Self {
+        self.reassembly_timeout = timeout;
+        self
+    }
+
+    /// Set how many bytes of incomplete messages are buffered per peer
+    ///
+    /// Besides the fragments themselves, each incomplete message counts a few bytes for every
+    /// fragment it announces. When a peer exceeds the cap, its oldest incomplete messages are
+    /// discarded.
+    pub fn max_pending_bytes(mut self, bytes: usize) -> Self {
+        self.max_pending_bytes = bytes;
+        self
+    }
+
+    /// Set how many incomplete messages are buffered per peer
+    ///
+    /// When a peer exceeds the cap, its oldest incomplete messages are discarded.
+    pub fn max_pending_messages(mut self, messages: usize) -> Self {
+        self.max_pending_messages = messages;
+        self
+    }
+
+    /// Set how many peers incomplete messages are buffered for at once
+    ///
+    /// When a fragment arrives from another peer while the cap is reached, the incomplete
+    /// messages of the peer that has waited longest are discarded.
+    pub fn max_pending_peers(mut self, peers: usize) -> Self {
+        self.max_pending_peers = peers;
+        self
+    }
+}
+
+impl Default for Fragmentation {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The header carried by each fragment: `message id (u32) | index (u16) | count (u16)`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Header {
+    message_id: u32,
+    index: u16,
+    count: u16,
+}
+
+impl Header {
+    fn write(&self, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(&self.message_id.to_be_bytes());
+        buf.extend_from_slice(&self.index.to_be_bytes());
+        buf.extend_from_slice(&self.count.to_be_bytes());
+    }
+
+    fn parse(datagram: &[u8]) -> Option<(Self, &[u8])> {
+        if datagram.len() < HEADER_LEN {
+            return None;
+        }
+        let (header, body) = datagram.split_at(HEADER_LEN);
+        let header = Self {
+            message_id: u32::from_be_bytes([header[0], header[1], header[2], header[3]]),
+            index: u16::from_be_bytes([header[4], header[5]]),
+            count: u16::from_be_bytes([header[6], header[7]]),
+        };
+        if header.count == 0 || header.index >= header.count {
+            return None;
+        }
+        Some((header, body))
+    }
+}
+
+/// Split a payload into datagrams of at most `datagram_size` bytes, each prefixed with a header
+pub(crate) fn split(
+    payload: &[u8],
+    message_id: u32,
+    datagram_size: usize,
+) -> Result<Vec<Vec<u8>>, UdpSocketError> {
+    let chunk_size = datagram_size.saturating_sub(HEADER_LEN);
+    let limit = chunk_size * u16::MAX as usize;
+    if chunk_size == 0 || payload.len() > limit {
+        return Err(UdpSocketError::MessageTooLarge {
+            size: payload.len(),
+            limit,
+        });
+    }
+
+    let count = ((payload.len() + chunk_size - 1) / chunk_size).max(1) as u16;
+    let datagrams = (0..count)
+        .map(|index| {
+            let start = index as usize * chunk_size;
+            let end = payload.len().min(start + chunk_size);
+            let mut datagram = Vec::with_capacity(HEADER_LEN + end - start);
+            Header {
+                message_id,
+                index,
+                count,
+            }
+            .write(&mut datagram);
+            datagram.extend_from_slice(&payload[start..end]);
+            datagram
+        })
+        .collect();
+    Ok(datagrams)
+}
+
+/// A message whose fragments haven't all arrived yet
+struct Partial {
+    message_id: u32,
+    started: Instant,
+    fragments: Vec<Option<Vec<u8>>>,
+    missing: usize,
+    bytes: usize,
+}
+
+impl Partial {
+    fn new(message_id: u32, count: u16, started: Instant) -> Self {
+        let count = count as usize;
+        Self {
+            message_id,
+            started,
+            fragments: vec![None; count],
+            missing: count,
+            // The slots are allocated up front, so they count before any fragment arrives
+            bytes: count * mem::size_of::<Option<Vec<u8>>>(),
+        }
+    }
+}
+
+/// Collects fragments per peer and yields complete payloads
+#[derive(Default)]
+pub(crate) struct Reassembler {
+    pending: HashMap<SocketAddr, Vec<Partial>>,
+    last_sweep: Option<Instant>,
+}
+
+impl Reassembler {
+    /// Add a fragment received from `from`, returning the payload once the message is complete
+    pub(crate) fn insert(
+        &mut self,
+        config: &Fragmentation,
+        from: SocketAddr,
+        datagram: &[u8],
+    ) -> Result<Option<Vec<u8>>, UdpSocketError> {
+        let (header, body) =
+            Header::parse(datagram).ok_or(UdpSocketError::MalformedFragment { from })?;
+
+        if header.count == 1 {
+            return Ok(Some(body.to_vec()));
+        }
+
+        let now = Instant::now();
+        self.sweep(config, now);
+        if !self.pending.contains_key(&from) && self.pending.len() >= config.max_pending_peers {
+            self.evict_oldest_peer();
+        }
+        let partials = self.pending.entry(from).or_default();
+        partials.retain(|partial| now.duration_since(partial.started) < config.reassembly_timeout);
+
+        let position = partials
+            .iter()
+            .position(|partial| partial.message_id == header.message_id);
+        let position = match position {
+            Some(position) if partials[position].fragments.len() != header.count as usize => {
+                partials.remove(position);
+                return Err(UdpSocketError::MalformedFragment { from });
+            }
+            Some(position) => position,
+            None => {
+                partials.push(Partial::new(header.message_id, header.count, now));
+                partials.len() - 1
+            }
+        };
+
+        let partial = &mut partials[position];
+        let slot = &mut partial.fragments[header.index as usize];
+        if slot.is_none() {
+            *slot = Some(body.to_vec());
+            partial.missing -= 1;
+            partial.bytes += body.len();
+        }
+
+        if partial.missing == 0 {
+            let partial = partials.remove(position);
+            if partials.is_empty() {
+                self.pending.remove(&from);
+            }
+            let payload = partial.fragments.into_iter().flatten().flatten().collect();
+            return Ok(Some(payload));
+        }
+
+        // Evict the oldest incomplete messages until the peer is back under its caps
+        let mut total: usize = partials.iter().map(|partial| partial.bytes).sum();
+        while total > config.max_pending_bytes || partials.len() > config.max_pending_messages {
+            let evicted = partials.remove(0);
+            total -= evicted.bytes;
+        }
+        if partials.is_empty() {
+            self.pending.remove(&from);
+        }
+        Ok(None)
+    }
+
+    /// Discard the timed out messages of every peer, at most once per reassembly timeout
+    ///
+    /// Peers only prune their own messages when they send again, so this keeps peers that went
+    /// quiet from holding on to memory.
+    fn sweep(&mut self, config: &Fragmentation, now: Instant) {
+        if self.last_sweep.map_or(false, |last| {
+            now.duration_since(last) < config.reassembly_timeout
+        }) {
+            return;
+        }
+        self.last_sweep = Some(now);
+        self.pending.retain(|_, partials| {
+            partials
+                .retain(|partial| now.duration_since(partial.started) < config.reassembly_timeout);
+            !partials.is_empty()
+        });
+    }
+
+    /// Discard the incomplete messages of the peer whose oldest message has waited longest
+    fn evict_oldest_peer(&mut self) {
+        let oldest = self
+            .pending
+            .iter()
+            .filter_map(|(peer, partials)| Some((*peer, partials.first()?.started)))
+            .min_by_key(|(_, started)| *started)
+            .map(|(peer, _)| peer);
+        if let Some(peer) = oldest {
+            self.pending.remove(&peer);
+        }
+    }
+}
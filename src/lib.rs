@@ -41,25 +41,206 @@
 //!    (a, b)
 //! }
 //! ```
-use bincode::{deserialize, serialize};
 use serde::de::DeserializeOwned;
 use serde::Serialize;
-use std::net::SocketAddr;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::net::{IpAddr, SocketAddr};
+use std::ops::RangeInclusive;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
 use thiserror::Error;
 use tokio::net::ToSocketAddrs;
 
+mod broadcast;
+mod checksum;
+mod codec;
+mod compression;
+mod encryption;
+mod envelope;
+mod fingerprint;
+mod fragment;
+mod max_size;
+mod message;
+mod multicast;
+mod noise;
+mod pipeline;
+mod registry;
+mod reliable;
+mod replay;
+mod rpc;
+mod sequence;
+mod server;
+mod signing;
+mod split;
+mod stream;
+mod typed;
+
+pub use checksum::Checksum;
+#[cfg(feature = "json")]
+pub use codec::JsonCodec;
+pub use codec::{BincodeCodec, Codec};
+pub use compression::Compression;
+pub use encryption::{Encryption, RejectPolicy};
+pub use envelope::Envelope;
+pub use fragment::Fragmentation;
+pub use max_size::{BoundedString, BoundedVec, MaxEncodedSize};
+pub use message::Message;
+pub use multicast::MulticastInterface;
+pub use noise::{Noise, NoiseKeypair};
+pub use registry::{AnyMessage, Dispatcher, Registry};
+pub use reliable::{Reliability, ReliableUdpSocket};
+pub use rpc::RpcClient;
+pub use sequence::Sequencing;
+pub use server::Server;
+pub use signing::Signing;
+#[cfg(feature = "derive")]
+pub use sockit_derive::{MaxEncodedSize, Message};
+pub use split::{RecvHalf, SendHalf};
+pub use stream::{UdpSink, UdpStream};
+pub use tokio_util::sync::CancellationToken;
+pub use typed::TypedUdpSocket;
+
+/// Items used by the code `sockit-derive` generates
+#[cfg(feature = "derive")]
+#[doc(hidden)]
+pub mod __private {
+    pub use crate::message::{MaxSizeOf, Unbounded};
+}
+
+use pipeline::{Inbound, Outbound};
+
+/// The largest payload that fits into a single UDP datagram over IPv4
+pub const MAX_DATAGRAM_SIZE: usize = 65_507;
+
+/// The receive buffer size used by [`UdpSocket::bind`] and [`UdpSocket::new`]
+pub const DEFAULT_BUFFER_SIZE: usize = 512;
+
 #[derive(Error, Debug)]
 pub enum UdpSocketError {
     #[error("`{0}`")]
     IoError(std::io::Error),
     #[error("`{0}`")]
     BincodeError(bincode::Error),
+    #[cfg(feature = "json")]
+    #[error("`{0}`")]
+    JsonError(serde_json::Error),
+    #[error("`{0}`")]
+    CodecError(Box<dyn std::error::Error + Send + Sync>),
+    #[error("datagram from {from} was truncated: received {received} bytes into a {capacity} byte buffer")]
+    Truncated {
+        received: usize,
+        capacity: usize,
+        from: SocketAddr,
+    },
+    #[error("message of {size} bytes exceeds the {limit} byte datagram limit")]
+    MessageTooLarge { size: usize, limit: usize },
+    #[error("malformed fragment received from {from}")]
+    MalformedFragment { from: SocketAddr },
+    #[error("malformed frame received from {from}")]
+    MalformedFrame { from: SocketAddr },
+    #[error("message to {to} was not acknowledged after {attempts} attempts")]
+    Unacknowledged { to: SocketAddr, attempts: u32 },
+    #[error("no response received from {to}")]
+    RpcTimeout { to: SocketAddr },
+    #[error("the RPC client's background task has stopped")]
+    RpcClosed,
+    #[error("{addr} is not a multicast address")]
+    NotMulticast { addr: IpAddr },
+    #[error("cannot write to broadcast address {addr} without enabling broadcast")]
+    BroadcastDisabled { addr: SocketAddr },
+    #[error("message from {from} decompresses to more than {limit} bytes")]
+    DecompressionLimitExceeded { from: SocketAddr, limit: usize },
+    #[error("message from {from} uses unsupported compression algorithm {flag}")]
+    UnsupportedCompression { from: SocketAddr, flag: u8 },
+    #[error("datagram from {from} failed authentication")]
+    AuthenticationFailed { from: SocketAddr },
+    #[error("datagram from {from} was replayed")]
+    ReplayDetected { from: SocketAddr },
+    #[error("no public key is known for the peer at {addr}")]
+    UnknownPeer { addr: SocketAddr },
+    #[error("handshake from {from} uses an untrusted public key")]
+    UntrustedPeer { from: SocketAddr },
+    #[error("value from {from} is unsigned or its signature is invalid")]
+    InvalidSignature { from: SocketAddr },
+    #[error("value from {from} is signed by an untrusted key")]
+    UntrustedSigner {
+        from: SocketAddr,
+        public_key: [u8; 32],
+    },
+    #[error("datagram from {from} failed its checksum")]
+    ChecksumMismatch { from: SocketAddr },
+    #[error("packet from {from} isn't a sockit value")]
+    ForeignPacket { from: SocketAddr },
+    #[error("packet from {from} uses unsupported wire version {version}")]
+    UnsupportedWireVersion { from: SocketAddr, version: u8 },
+    #[error("packet from {from} uses protocol version {got}, expected {expected:?}")]
+    ProtocolVersionMismatch {
+        from: SocketAddr,
+        expected: RangeInclusive<u16>,
+        got: u16,
+    },
+    #[error("packet from {from} holds message type {got:#010x}, expected {expected:#010x}")]
+    UnexpectedMessageType {
+        from: SocketAddr,
+        expected: u32,
+        got: u32,
+    },
+    #[error("type {type_name} isn't registered")]
+    UnregisteredMessageType { type_name: &'static str },
+    #[error("packet from {from} holds unregistered message tag {tag}")]
+    UnknownMessageTag { from: SocketAddr, tag: u32 },
+    #[error("no message registry is set")]
+    NoRegistry,
+    #[error("value from {from} has type fingerprint {got:#018x}, expected {expected:#018x}")]
+    TypeMismatch {
+        from: SocketAddr,
+        expected: u64,
+        got: u64,
+    },
+    #[error("signature verification isn't enabled")]
+    VerificationDisabled,
+    #[error(
+        "sequencing isn't supported by reliable sockets and RPC, which number their own frames"
+    )]
+    SequencingUnsupported,
+}
+
+impl UdpSocketError {
+    /// The address of the peer whose datagram caused the error, if a datagram caused it
+    pub(crate) fn sender(&self) -> Option<SocketAddr> {
+        match self {
+            Self::Truncated { from, .. }
+            | Self::MalformedFragment { from }
+            | Self::MalformedFrame { from }
+            | Self::DecompressionLimitExceeded { from, .. }
+            | Self::UnsupportedCompression { from, .. }
+            | Self::AuthenticationFailed { from }
+            | Self::ReplayDetected { from }
+            | Self::UntrustedPeer { from }
+            | Self::InvalidSignature { from }
+            | Self::UntrustedSigner { from, .. }
+            | Self::ChecksumMismatch { from }
+            | Self::ForeignPacket { from }
+            | Self::UnsupportedWireVersion { from, .. }
+            | Self::ProtocolVersionMismatch { from, .. }
+            | Self::UnexpectedMessageType { from, .. }
+            | Self::UnknownMessageTag { from, .. }
+            | Self::TypeMismatch { from, .. } => Some(*from),
+            _ => None,
+        }
+    }
 }
 
 /// A high-level UDP Socket that allows for writing and reading (de)serializable values
-pub struct UdpSocket {
-    buffer: [u8; 512],
-    socket: tokio::net::UdpSocket,
+///
+/// Values are (de)serialized with the socket's [`Codec`], which is [`BincodeCodec`] by default.
+pub struct UdpSocket<C = BincodeCodec> {
+    codec: C,
+    outbound: Outbound,
+    inbound: Inbound,
+    registry: Option<Arc<Registry<C>>>,
 }
 
 impl UdpSocket {
@@ -95,12 +276,332 @@ impl UdpSocket {
     ///   Ok(())
     /// }
     pub fn new(socket: tokio::net::UdpSocket) -> Self {
-        let buffer = [0; 512];
-        Self { buffer, socket }
+        Self::with_capacity(socket, DEFAULT_BUFFER_SIZE)
+    }
+
+    /// Create a new UDP socket from an existing [`tokio::net::UdpSocket`] that can receive
+    /// datagrams of up to `capacity` bytes
+    ///
+    /// # Panics
+    ///
+    /// Panics if `capacity` is zero or larger than [`MAX_DATAGRAM_SIZE`].
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use sockit::UdpSocket;
+    /// use tokio::net::UdpSocket as TokioUdpSocket;
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    ///   let tokio_socket = TokioUdpSocket::bind("127.0.0.1:0").await?;
+    ///   let mut sockit_socket = UdpSocket::with_capacity(tokio_socket, 8192);
+    ///   Ok(())
+    /// }
+    /// ```
+    pub fn with_capacity(socket: tokio::net::UdpSocket, capacity: usize) -> Self {
+        assert!(
+            capacity > 0 && capacity <= MAX_DATAGRAM_SIZE,
+            "capacity must be between 1 and {MAX_DATAGRAM_SIZE} bytes, got {capacity}"
+        );
+        let socket = Arc::new(socket);
+        Self {
+            codec: BincodeCodec,
+            outbound: Outbound::new(socket.clone(), capacity),
+            inbound: Inbound::new(socket, capacity),
+            registry: None,
+        }
+    }
+}
+
+impl<C: Codec> UdpSocket<C> {
+    /// Replace the [`Codec`] used to (de)serialize values on this socket
+    ///
+    /// Both peers must use the same codec to understand each other. The [`Registry`] of the
+    /// socket is removed, since it is bound to the previous codec.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use sockit::{BincodeCodec, UdpSocket};
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    ///   let socket = UdpSocket::bind("127.0.0.1:0").await?.with_codec(BincodeCodec);
+    ///   Ok(())
+    /// }
+    /// ```
+    pub fn with_codec<D: Codec>(mut self, codec: D) -> UdpSocket<D> {
+        self.outbound.tags = None;
+        self.inbound.checks.tags = None;
+        UdpSocket {
+            codec,
+            outbound: self.outbound,
+            inbound: self.inbound,
+            registry: None,
+        }
+    }
+
+    /// Get a reference to the [`Codec`] used by this socket
+    pub fn codec(&self) -> &C {
+        &self.codec
+    }
+
+    /// Get the size of the largest datagram this socket can receive
+    pub fn capacity(&self) -> usize {
+        self.inbound.capacity()
+    }
+
+    /// Set the size of the largest datagram this socket is allowed to send
+    ///
+    /// Defaults to [`MAX_DATAGRAM_SIZE`]. Lower it to stay below the path MTU and avoid
+    /// IP fragmentation.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `size` is zero or larger than [`MAX_DATAGRAM_SIZE`].
+    pub fn set_max_datagram_size(&mut self, size: usize) {
+        assert!(
+            size > 0 && size <= MAX_DATAGRAM_SIZE,
+            "datagram size must be between 1 and {MAX_DATAGRAM_SIZE} bytes, got {size}"
+        );
+        self.outbound.max_datagram_size = size;
+    }
+
+    /// Get the size of the largest datagram this socket is allowed to send
+    pub fn max_datagram_size(&self) -> usize {
+        self.outbound.max_datagram_size
+    }
+
+    /// Set the receive capacity of the peers this socket writes to
+    ///
+    /// Defaults to this socket's own [capacity](Self::capacity), which assumes that both
+    /// ends of the conversation were created with the same buffer size.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `capacity` is zero or larger than [`MAX_DATAGRAM_SIZE`].
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use sockit::UdpSocket;
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    ///   let mut socket = UdpSocket::bind("127.0.0.1:0").await?;
+    ///   socket.set_peer_capacity(8192);
+    ///   Ok(())
+    /// }
+    /// ```
+    pub fn set_peer_capacity(&mut self, capacity: usize) {
+        assert!(
+            capacity > 0 && capacity <= MAX_DATAGRAM_SIZE,
+            "capacity must be between 1 and {MAX_DATAGRAM_SIZE} bytes, got {capacity}"
+        );
+        self.outbound.peer_capacity = capacity;
+    }
+
+    /// Get the receive capacity of the peers this socket writes to
+    pub fn peer_capacity(&self) -> usize {
+        self.outbound.peer_capacity
+    }
+
+    /// Get the size of the largest datagram [`write`](Self::write) will send, which is the
+    /// smaller of [`max_datagram_size`](Self::max_datagram_size) and
+    /// [`peer_capacity`](Self::peer_capacity)
+    pub fn send_limit(&self) -> usize {
+        self.outbound.send_limit()
+    }
+
+    /// Split values that don't fit into a single datagram into several fragments
+    ///
+    /// See [`Fragmentation`] for details. Both peers must enable fragmentation.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use sockit::{Fragmentation, UdpSocket};
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    ///   let mut socket = UdpSocket::bind("127.0.0.1:0").await?;
+    ///   socket.set_fragmentation(Fragmentation::new());
+    ///   Ok(())
+    /// }
+    /// ```
+    pub fn set_fragmentation(&mut self, fragmentation: Fragmentation) {
+        self.outbound.fragmentation = true;
+        self.inbound.fragmentation = Some(fragmentation);
+    }
+
+    /// Get the fragmentation configuration of this socket, if it is enabled
+    pub fn fragmentation(&self) -> Option<&Fragmentation> {
+        self.inbound.fragmentation.as_ref()
+    }
+
+    /// Discard duplicate values and order the values received from each peer
+    ///
+    /// See [`Sequencing`] for the available modes. Both peers must enable sequencing.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use sockit::{Sequencing, UdpSocket};
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    ///   let mut socket = UdpSocket::bind("127.0.0.1:0").await?;
+    ///   socket.set_sequencing(Sequencing::Latest);
+    ///   Ok(())
+    /// }
+    /// ```
+    pub fn set_sequencing(&mut self, sequencing: Sequencing) {
+        self.outbound.sequencing = true;
+        self.inbound.sequencing = Some(sequencing);
+    }
+
+    /// Get the sequencing mode of this socket, if it is enabled
+    pub fn sequencing(&self) -> Option<Sequencing> {
+        self.inbound.sequencing
+    }
+
+    /// Compress values before they are sent and decompress received ones
+    ///
+    /// See [`Compression`] for the available algorithms. Both peers must enable compression.
+    pub fn set_compression(&mut self, compression: Compression) {
+        self.outbound.compression = Some(compression);
+        self.inbound.compression = Some(compression);
+    }
+
+    /// Get the compression configuration of this socket, if it is enabled
+    pub fn compression(&self) -> Option<&Compression> {
+        self.outbound.compression.as_ref()
+    }
+
+    /// Encrypt and authenticate every datagram with a pre-shared key
+    ///
+    /// Both peers must use the same key. This replaces any [Noise](Self::set_noise)
+    /// configuration. Encryption adds 40 bytes to every datagram, which
+    /// count towards the [send limit](Self::send_limit). See [`Encryption`] for details.
+    pub fn set_encryption(&mut self, encryption: Encryption) {
+        self.outbound.encryption = Some(encryption::Sealer::new(encryption.clone()));
+        self.outbound.noise = None;
+        self.inbound.set_encryption(encryption);
+    }
+
+    /// Get the encryption configuration of this socket, if it is enabled
+    pub fn encryption(&self) -> Option<&Encryption> {
+        self.inbound.encryption()
+    }
+
+    /// Encrypt datagrams with per-peer session keys negotiated by Noise handshakes
+    ///
+    /// This replaces any pre-shared key set with [`set_encryption`](Self::set_encryption).
+    /// Sessions add 29 bytes to every datagram, which count towards the
+    /// [send limit](Self::send_limit). See [`Noise`] for details.
+    pub fn set_noise(&mut self, noise: Noise) {
+        let sessions = Arc::new(noise::NoiseSessions::new(noise));
+        self.outbound.noise = Some(sessions.clone());
+        self.outbound.encryption = None;
+        self.inbound.set_noise(sessions);
+    }
+
+    /// Get the Noise configuration of this socket, if it is enabled
+    pub fn noise(&self) -> Option<&Noise> {
+        self.inbound.noise().map(|sessions| &sessions.config)
+    }
+
+    /// Append a checksum to every datagram and verify it on received ones
+    ///
+    /// See [`Checksum`] for the available algorithms. Both peers must use the same checksum.
+    pub fn set_checksum(&mut self, checksum: Checksum) {
+        self.outbound.checksum = Some(checksum);
+        self.inbound.checksum = Some(checksum);
+    }
+
+    /// Get the checksum of this socket, if it is enabled
+    pub fn checksum(&self) -> Option<Checksum> {
+        self.inbound.checksum
+    }
+
+    /// Write every value in a versioned envelope and check the envelope of read ones
+    ///
+    /// See [`Envelope`] for details. Both peers must use an envelope, which adds 7 bytes to
+    /// every value.
+    pub fn set_envelope(&mut self, envelope: Envelope) {
+        self.outbound.envelope = Some(envelope.clone());
+        self.inbound.checks.envelope = Some(envelope);
+    }
+
+    /// Get the envelope configuration of this socket, if it is enabled
+    pub fn envelope(&self) -> Option<&Envelope> {
+        self.inbound.checks.envelope.as_ref()
+    }
+
+    /// Write a fingerprint of the type of every value and check it on read ones
+    ///
+    /// Codecs like bincode aren't self-describing, so a value of one type can decode as another
+    /// type whose encoding happens to line up. With fingerprinting enabled, [`read`](Self::read)
+    /// reports such values as [`UdpSocketError::TypeMismatch`] instead. The fingerprint is
+    /// derived from the serde shape of the type, meaning its name and the names and types of its
+    /// fields, and adds 8 bytes to every value. Both peers must enable it.
+    ///
+    /// For types outside a [`Registry`], the fingerprint is traced from every value as it is
+    /// encoded and decoded, so it also covers the types inside the options, collections and enum
+    /// variants the value holds. The fingerprint of a registered type is traced once from its
+    /// full schema, and that of a [`Message`] is its declared one if it has one.
+    ///
+    /// Outside a [`Registry`], types whose `Deserialize` impl relies on `deserialize_any`, like
+    /// internally tagged and untagged enums, can't be traced like they were written and are
+    /// always rejected.
+    pub fn set_fingerprinting(&mut self, enabled: bool) {
+        self.outbound.fingerprinting = enabled;
+        self.inbound.checks.fingerprinting = enabled;
+    }
+
+    /// Get whether values are fingerprinted
+    pub fn fingerprinting(&self) -> bool {
+        self.inbound.checks.fingerprinting
+    }
+
+    /// Tag every value with its type from a registry of message types
+    ///
+    /// See [`Registry`] for details. Once it is set, values of any registered type can be read
+    /// with [`read_any`](Self::read_any), and values of unregistered types can no longer be
+    /// written or read.
+    pub fn set_registry(&mut self, registry: Registry<C>) {
+        self.outbound.tags = Some(registry.tags());
+        self.inbound.checks.tags = Some(registry.tags());
+        self.registry = Some(Arc::new(registry));
+    }
+
+    /// Get the message registry of this socket, if it is set
+    pub fn registry(&self) -> Option<&Registry<C>> {
+        self.registry.as_deref()
+    }
+
+    /// Sign written values and verify read ones with Ed25519 keys
+    ///
+    /// See [`Signing`] for details. Signatures add 96 bytes to every value.
+    pub fn set_signing(&mut self, signing: Signing) {
+        let signing = Arc::new(signing);
+        self.outbound.signing = Some(signing.clone());
+        self.inbound.signing = Some(signing);
+    }
+
+    /// Get the signing configuration of this socket, if it is enabled
+    pub fn signing(&self) -> Option<&Signing> {
+        self.inbound.signing.as_deref()
     }
 
     /// Write a serializable value to the socket
     ///
+    /// The value is checked against the socket's [send limit](Self::send_limit) before anything
+    /// is sent, and [`UdpSocketError::MessageTooLarge`] is returned if it doesn't fit. When
+    /// [fragmentation](Self::set_fragmentation) is enabled, the value is split into several
+    /// datagrams instead.
+    ///
     /// # Example
     ///
     /// ```no_run
@@ -118,15 +619,19 @@ impl UdpSocket {
         value: &T,
         send_to: SocketAddr,
     ) -> Result<(), UdpSocketError> {
-        let buf = serialize(value)?;
-        self.socket.send_to(buf.as_slice(), send_to).await?;
-        Ok(())
+        let frame = self.outbound.value_frame(&self.codec, value, send_to)?;
+        self.send_frame(&frame, send_to).await
     }
 
     /// Read a deserializable value from a single datagram on the socket
     ///
     /// This method returns an error when it isn't possible to deserialize the value
-    /// from the datagram. This can happen if the value doesn't fit into a single datagram.
+    /// from the datagram. If the datagram is larger than the socket's [capacity](Self::capacity),
+    /// [`UdpSocketError::Truncated`] is returned instead of attempting to deserialize it.
+    ///
+    /// When [fragmentation](Self::set_fragmentation) is enabled, this method waits until all
+    /// fragments of a value have arrived. When [sequencing](Self::set_sequencing) is enabled,
+    /// duplicate and out of order values are handled according to the [`Sequencing`] mode.
     ///
     /// # Example
     ///
@@ -141,11 +646,81 @@ impl UdpSocket {
     /// }
     ///```
     pub async fn read<T: DeserializeOwned>(&mut self) -> Result<(T, SocketAddr), UdpSocketError> {
-        let (_, src) = self.socket.recv_from(&mut self.buffer).await?;
-        let value = deserialize::<T>(self.buffer.as_slice())?;
+        let (frame, src) = self.inbound.recv_value_frame().await?;
+        let value = self.inbound.decode_value(&self.codec, &frame, src)?;
         Ok((value, src))
     }
 
+    /// Read a value of whichever type in the socket's [`Registry`] arrives next
+    ///
+    /// Values with a tag that isn't registered are reported as
+    /// [`UdpSocketError::UnknownMessageTag`], and without a registry this fails with
+    /// [`UdpSocketError::NoRegistry`].
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use sockit::{Registry, UdpSocket};
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    ///   let mut socket = UdpSocket::bind("127.0.0.1:0").await?;
+    ///   socket.set_registry(Registry::new().register::<String>(1).register::<u64>(2));
+    ///   let (message, from) = socket.read_any().await?;
+    ///   if let Ok(n) = message.downcast::<u64>() {
+    ///       println!("{from} sent {n}");
+    ///   }
+    ///   Ok(())
+    /// }
+    /// ```
+    pub async fn read_any(&mut self) -> Result<(AnyMessage, SocketAddr), UdpSocketError> {
+        registry::read_any(&mut self.inbound, &self.codec, self.registry.as_deref()).await
+    }
+
+    /// Read a deserializable value and the Ed25519 public key of the node that signed it
+    ///
+    /// Signature verification must be enabled by trusting at least one key with
+    /// [`set_signing`](Self::set_signing). Values that aren't signed by a trusted key are
+    /// handled according to the [`Signing`] configuration.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use sockit::{Signing, UdpSocket};
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    ///   let mut socket = UdpSocket::bind("127.0.0.1:0").await?;
+    ///   socket.set_signing(Signing::new().trust([0; 32]));
+    ///   let (message, from, signer) = socket.read_signed::<String>().await?;
+    ///   Ok(())
+    /// }
+    /// ```
+    pub async fn read_signed<T: DeserializeOwned>(
+        &mut self,
+    ) -> Result<(T, SocketAddr, [u8; 32]), UdpSocketError> {
+        if !self.signing().map_or(false, Signing::verifies) {
+            return Err(UdpSocketError::VerificationDisabled);
+        }
+        let (frame, src, signer) = self.inbound.recv_signed_value_frame().await?;
+        let value = self.inbound.decode_value(&self.codec, &frame, src)?;
+        let signer = signer.expect("signatures are verified");
+        Ok((value, src, signer))
+    }
+
+    /// Send an encoded message, splitting it into fragments if fragmentation is enabled
+    async fn send_frame(&self, frame: &[u8], send_to: SocketAddr) -> Result<(), UdpSocketError> {
+        self.outbound.send_frame(frame, send_to).await
+    }
+
+    /// Fail if sequencing is enabled, for protocols that number their own frames
+    fn reject_sequencing(&self) -> Result<(), UdpSocketError> {
+        match self.outbound.sequencing || self.inbound.sequencing.is_some() {
+            true => Err(UdpSocketError::SequencingUnsupported),
+            false => Ok(()),
+        }
+    }
+
     /// Get the local address of the socket
     ///
     /// # Example
@@ -161,16 +736,43 @@ impl UdpSocket {
     /// }
     /// ```
     pub fn local_addr(&self) -> Result<SocketAddr, UdpSocketError> {
-        Ok(self.socket.local_addr()?)
+        Ok(self.outbound.socket.local_addr()?)
     }
 }
 
+/// Generate a random number from the standard library's randomly seeded hasher
+fn random_u64() -> u64 {
+    RandomState::new().build_hasher().finish()
+}
+
+/// Get the time in nanoseconds since the Unix epoch, bumped past the previous timestamp taken by
+/// this process so that timestamps strictly increase
+fn unique_timestamp() -> u64 {
+    static LAST: AtomicU64 = AtomicU64::new(0);
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_nanos() as u64);
+    let previous = LAST
+        .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |last| {
+            Some(now.max(last + 1))
+        })
+        .unwrap();
+    now.max(previous + 1)
+}
+
 impl From<Box<bincode::ErrorKind>> for UdpSocketError {
     fn from(e: Box<bincode::ErrorKind>) -> Self {
         UdpSocketError::BincodeError(e)
     }
 }
 
+#[cfg(feature = "json")]
+impl From<serde_json::Error> for UdpSocketError {
+    fn from(e: serde_json::Error) -> Self {
+        UdpSocketError::JsonError(e)
+    }
+}
+
 impl From<std::io::Error> for UdpSocketError {
     fn from(e: std::io::Error) -> Self {
         UdpSocketError::IoError(e)
@@ -0,0 +1,311 @@
+use crate::{BincodeCodec, UdpSocket, UdpSocketError, MAX_DATAGRAM_SIZE};
+use serde::de::{Deserialize, Deserializer, Error};
+use serde::{Serialize, Serializer};
+use std::marker::PhantomData;
+use std::net::SocketAddr;
+use std::ops::Deref;
+
+/// The largest number of bytes a value of a type can encode to with [`BincodeCodec`]
+///
+/// Implemented for primitives, options, tuples, fixed-size arrays and the bounded collections
+/// [`BoundedVec`] and [`BoundedString`], whose length is limited by their type. Types built from
+/// these can implement it by adding up their fields, which `#[derive(sockit::MaxEncodedSize)]`
+/// does with the `derive` feature. Unbounded types like [`Vec`] and [`String`] don't implement
+/// it.
+///
+/// [`write_bounded`](UdpSocket::write_bounded) uses it to prove at compile time that every value
+/// of a type fits into a datagram, and [`TypedUdpSocket::bind`](crate::TypedUdpSocket::bind) to
+/// size the receive buffer exactly.
+///
+/// # Example
+///
+/// ```no_run
+/// use serde::{Deserialize, Serialize};
+/// use sockit::{BoundedString, MaxEncodedSize, UdpSocket};
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct Reading {
+///     sensor: BoundedString<16>,
+///     value: f64,
+/// }
+///
+/// impl MaxEncodedSize for Reading {
+///     const MAX_ENCODED_SIZE: usize =
+///         BoundedString::<16>::MAX_ENCODED_SIZE + f64::MAX_ENCODED_SIZE;
+/// }
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+///   let mut socket = UdpSocket::bind("127.0.0.1:0").await?;
+///   let reading = Reading { sensor: "thermometer".try_into()?, value: 21.5 };
+///   socket.write_bounded(&reading, "127.0.0.1:9090".parse()?).await?;
+///   Ok(())
+/// }
+/// ```
+pub trait MaxEncodedSize {
+    /// The largest number of bytes a value of this type encodes to
+    const MAX_ENCODED_SIZE: usize;
+}
+
+macro_rules! fixed_size {
+    ($($ty:ty => $size:expr),* $(,)?) => {
+        $(
+            impl MaxEncodedSize for $ty {
+                const MAX_ENCODED_SIZE: usize = $size;
+            }
+        )*
+    };
+}
+
+// bincode encodes `usize` and `isize` as 64-bit integers and `char` as UTF-8
+fixed_size! {
+    () => 0,
+    bool => 1,
+    u8 => 1,
+    i8 => 1,
+    u16 => 2,
+    i16 => 2,
+    u32 => 4,
+    i32 => 4,
+    f32 => 4,
+    u64 => 8,
+    i64 => 8,
+    f64 => 8,
+    usize => 8,
+    isize => 8,
+    u128 => 16,
+    i128 => 16,
+    char => 4,
+}
+
+impl<T: ?Sized> MaxEncodedSize for PhantomData<T> {
+    const MAX_ENCODED_SIZE: usize = 0;
+}
+
+impl<T: MaxEncodedSize + ?Sized> MaxEncodedSize for Box<T> {
+    const MAX_ENCODED_SIZE: usize = T::MAX_ENCODED_SIZE;
+}
+
+impl<T: MaxEncodedSize> MaxEncodedSize for Option<T> {
+    const MAX_ENCODED_SIZE: usize = T::MAX_ENCODED_SIZE.saturating_add(1);
+}
+
+impl<T: MaxEncodedSize, E: MaxEncodedSize> MaxEncodedSize for Result<T, E> {
+    const MAX_ENCODED_SIZE: usize =
+        max(T::MAX_ENCODED_SIZE, E::MAX_ENCODED_SIZE).saturating_add(ENUM_TAG_LEN);
+}
+
+impl<T: MaxEncodedSize, const N: usize> MaxEncodedSize for [T; N] {
+    const MAX_ENCODED_SIZE: usize = T::MAX_ENCODED_SIZE.saturating_mul(N);
+}
+
+macro_rules! tuple {
+    ($($name:ident)+) => {
+        impl<$($name: MaxEncodedSize),+> MaxEncodedSize for ($($name,)+) {
+            const MAX_ENCODED_SIZE: usize = 0usize $(.saturating_add($name::MAX_ENCODED_SIZE))+;
+        }
+    };
+}
+
+tuple!(A);
+tuple!(A B);
+tuple!(A B C);
+tuple!(A B C D);
+tuple!(A B C D E);
+tuple!(A B C D E F);
+tuple!(A B C D E F G);
+tuple!(A B C D E F G H);
+
+/// Proves that every value of a type fits into a datagram, failing to compile where `CHECKED`
+/// is used for a type that doesn't
+pub(crate) struct FitsDatagram<T>(PhantomData<T>);
+
+impl<T: MaxEncodedSize> FitsDatagram<T> {
+    pub(crate) const CHECKED: () = assert!(
+        T::MAX_ENCODED_SIZE <= MAX_DATAGRAM_SIZE,
+        "values of this type may not fit into a datagram"
+    );
+}
+
+/// The number of bytes bincode encodes the length of a sequence or string with
+const LENGTH_LEN: usize = 8;
+
+/// The number of bytes bincode encodes the variant of an enum with
+const ENUM_TAG_LEN: usize = 4;
+
+/// The larger of two sizes, usable in constants
+const fn max(a: usize, b: usize) -> usize {
+    if a > b {
+        a
+    } else {
+        b
+    }
+}
+
+/// A [`Vec`] holding at most `N` elements, so that its encoded size is bounded
+///
+/// Values holding more than `N` elements fail to deserialize.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct BoundedVec<T, const N: usize>(Vec<T>);
+
+impl<T, const N: usize> BoundedVec<T, N> {
+    /// Create an empty vector
+    pub fn new() -> Self {
+        Self(Vec::new())
+    }
+
+    /// Append an element, or give it back if the vector is full
+    pub fn push(&mut self, value: T) -> Result<(), T> {
+        if self.0.len() == N {
+            return Err(value);
+        }
+        self.0.push(value);
+        Ok(())
+    }
+
+    /// Unwrap the underlying [`Vec`]
+    pub fn into_inner(self) -> Vec<T> {
+        self.0
+    }
+}
+
+impl<T, const N: usize> Default for BoundedVec<T, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T, const N: usize> Deref for BoundedVec<T, N> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        &self.0
+    }
+}
+
+impl<T, const N: usize> TryFrom<Vec<T>> for BoundedVec<T, N> {
+    type Error = Vec<T>;
+
+    /// Wrap a [`Vec`], or give it back if it holds more than `N` elements
+    fn try_from(vec: Vec<T>) -> Result<Self, Vec<T>> {
+        match vec.len() <= N {
+            true => Ok(Self(vec)),
+            false => Err(vec),
+        }
+    }
+}
+
+impl<T: MaxEncodedSize, const N: usize> MaxEncodedSize for BoundedVec<T, N> {
+    const MAX_ENCODED_SIZE: usize =
+        LENGTH_LEN.saturating_add(T::MAX_ENCODED_SIZE.saturating_mul(N));
+}
+
+impl<T: Serialize, const N: usize> Serialize for BoundedVec<T, N> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.0.serialize(serializer)
+    }
+}
+
+impl<'de, T: Deserialize<'de>, const N: usize> Deserialize<'de> for BoundedVec<T, N> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let vec = Vec::deserialize(deserializer)?;
+        Self::try_from(vec).map_err(|vec| {
+            D::Error::invalid_length(vec.len(), &format!("at most {N} elements").as_str())
+        })
+    }
+}
+
+/// A [`String`] of at most `N` bytes, so that its encoded size is bounded
+///
+/// Values longer than `N` bytes fail to deserialize.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct BoundedString<const N: usize>(String);
+
+impl<const N: usize> BoundedString<N> {
+    /// Create an empty string
+    pub fn new() -> Self {
+        Self(String::new())
+    }
+
+    /// Get the string as a `&str`
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Unwrap the underlying [`String`]
+    pub fn into_inner(self) -> String {
+        self.0
+    }
+}
+
+impl<const N: usize> Deref for BoundedString<N> {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl<const N: usize> TryFrom<String> for BoundedString<N> {
+    type Error = String;
+
+    /// Wrap a [`String`], or give it back if it is longer than `N` bytes
+    fn try_from(string: String) -> Result<Self, String> {
+        match string.len() <= N {
+            true => Ok(Self(string)),
+            false => Err(string),
+        }
+    }
+}
+
+impl<const N: usize> TryFrom<&str> for BoundedString<N> {
+    type Error = String;
+
+    fn try_from(string: &str) -> Result<Self, String> {
+        Self::try_from(string.to_string())
+    }
+}
+
+impl<const N: usize> MaxEncodedSize for BoundedString<N> {
+    const MAX_ENCODED_SIZE: usize = LENGTH_LEN.saturating_add(N);
+}
+
+impl<const N: usize> Serialize for BoundedString<N> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.0.serialize(serializer)
+    }
+}
+
+impl<'de, const N: usize> Deserialize<'de> for BoundedString<N> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let string = String::deserialize(deserializer)?;
+        Self::try_from(string).map_err(|string| {
+            D::Error::invalid_length(string.len(), &format!("at most {N} bytes").as_str())
+        })
+    }
+}
+
+impl UdpSocket<BincodeCodec> {
+    /// Write a value whose type is proven to fit into a datagram
+    ///
+    /// Types that may encode to more than [`MAX_DATAGRAM_SIZE`] bytes are rejected at compile
+    /// time. Before anything is encoded, the type's maximum size is checked against the room
+    /// left for values under the socket's current configuration, so that
+    /// [`UdpSocketError::MessageTooLarge`] is returned for every value of a type that might not
+    /// fit, rather than only for the values that happen to be too large.
+    pub async fn write_bounded<T: Serialize + MaxEncodedSize>(
+        &mut self,
+        value: &T,
+        send_to: SocketAddr,
+    ) -> Result<(), UdpSocketError> {
+        let () = FitsDatagram::<T>::CHECKED;
+        let limit = self.outbound.value_limit();
+        if T::MAX_ENCODED_SIZE > limit {
+            return Err(UdpSocketError::MessageTooLarge {
+                size: T::MAX_ENCODED_SIZE,
+                limit,
+            });
+        }
+        self.write(value, send_to).await
+    }
+}
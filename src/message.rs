@@ -0,0 +1,82 @@
+#[cfg(feature = "derive")]
+use crate::MaxEncodedSize;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+#[cfg(feature = "derive")]
+use std::marker::PhantomData;
+
+/// A message type with a stable id, a schema fingerprint and an optional maximum encoded size
+///
+/// Message types are registered with [`Registry::register_message`](crate::Registry::register_message),
+/// after which [`write`](crate::UdpSocket::write) and [`read`](crate::UdpSocket::read) use the
+/// id as the type's tag and, when [fingerprinting](crate::UdpSocket::set_fingerprinting) is
+/// enabled, the fingerprint in place of the traced one, if declared. Values encoding to more than the
+/// maximum size are refused by `write` with [`UdpSocketError::MessageTooLarge`](crate::UdpSocketError::MessageTooLarge).
+///
+/// With the `derive` feature, `#[derive(sockit::Message)]` implements this trait. The id
+/// defaults to a hash of the type's name and can be set with `#[message(id = ...)]`. The maximum
+/// size defaults to the [`MaxEncodedSize`](crate::MaxEncodedSize) of types implementing it and
+/// can be declared with `#[message(max_size = ...)]`, which fails to compile if it is below that
+/// bound, since some values of the type would then be refused. Derived types don't declare a
+/// fingerprint, so theirs is traced from their full schema, which covers the types of their
+/// fields down to the leaves.
+///
+/// # Example
+///
+/// ```no_run
+/// use serde::{Deserialize, Serialize};
+/// use sockit::{Message, Registry, UdpSocket};
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct Ping(u32);
+///
+/// // Usually written as `#[derive(sockit::Message)]` and `#[message(id = 1, max_size = 4)]`
+/// impl Message for Ping {
+///     const ID: u32 = 1;
+///     const MAX_SIZE: Option<usize> = Some(4);
+/// }
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+///   let mut socket = UdpSocket::bind("127.0.0.1:0").await?;
+///   socket.set_registry(Registry::new().register_message::<Ping>());
+///   socket.write(&Ping(1), "127.0.0.1:9090".parse()?).await?;
+///   Ok(())
+/// }
+/// ```
+pub trait Message: Serialize + DeserializeOwned + Send + 'static {
+    /// The tag identifying this type on the wire
+    const ID: u32;
+    /// A fingerprint replacing the one traced from the type's schema, if declared
+    ///
+    /// A declared fingerprint only changes when it is changed by hand, so it is up to the
+    /// author to change it along with the encoding of the type.
+    const FINGERPRINT: Option<u64> = None;
+    /// The size in bytes that values of this type never exceed once encoded with
+    /// [`BincodeCodec`](crate::BincodeCodec), if declared
+    ///
+    /// Other codecs encode values to different sizes, so the limit is only enforced for codecs
+    /// that [honor](crate::Codec::honors_max_encoded_size) it.
+    const MAX_SIZE: Option<usize> = None;
+}
+
+/// Finds the [`MaxEncodedSize`] of a type for `#[derive(sockit::Message)]`, if it has one
+#[cfg(feature = "derive")]
+#[doc(hidden)]
+pub struct MaxSizeOf<T>(PhantomData<T>);
+
+#[cfg(feature = "derive")]
+impl<T: MaxEncodedSize> MaxSizeOf<T> {
+    pub const MAX_SIZE: Option<usize> = Some(T::MAX_ENCODED_SIZE);
+}
+
+/// The fallback for types without a [`MaxEncodedSize`], whose constant is only used when the
+/// inherent one of [`MaxSizeOf`] doesn't apply
+#[cfg(feature = "derive")]
+#[doc(hidden)]
+pub trait Unbounded {
+    const MAX_SIZE: Option<usize> = None;
+}
+
+#[cfg(feature = "derive")]
+impl<T> Unbounded for MaxSizeOf<T> {}
@@ -0,0 +1,175 @@
+use crate::{Codec, UdpSocket, UdpSocketError};
+use serde::Serialize;
+use socket2::SockRef;
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+
+/// The local interface on which a multicast group is joined or left
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MulticastInterface {
+    /// Let the operating system choose an appropriate interface
+    Any,
+    /// The IPv4 interface with this address, for IPv4 groups
+    V4(Ipv4Addr),
+    /// The interface with this index, for IPv6 groups
+    V6(u32),
+}
+
+impl Default for MulticastInterface {
+    fn default() -> Self {
+        Self::Any
+    }
+}
+
+impl<C: Codec> UdpSocket<C> {
+    /// Join the multicast group `group` on the given interface
+    ///
+    /// Values written to the group are received by [`read`](Self::read) once the socket has
+    /// joined it. The socket must be bound to the port the group's values are sent to.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use sockit::{MulticastInterface, UdpSocket};
+    /// use std::net::Ipv4Addr;
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    ///   let mut socket = UdpSocket::bind("0.0.0.0:9090").await?;
+    ///   socket.join_multicast("239.255.0.1".parse()?, MulticastInterface::Any)?;
+    ///   let (message, from) = socket.read::<String>().await?;
+    ///   Ok(())
+    /// }
+    /// ```
+    pub fn join_multicast(
+        &self,
+        group: IpAddr,
+        interface: MulticastInterface,
+    ) -> Result<(), UdpSocketError> {
+        let socket = &self.outbound.socket;
+        match (multicast_group(group)?, interface) {
+            (IpAddr::V4(group), MulticastInterface::Any) => {
+                socket.join_multicast_v4(group, Ipv4Addr::UNSPECIFIED)?
+            }
+            (IpAddr::V4(group), MulticastInterface::V4(interface)) => {
+                socket.join_multicast_v4(group, interface)?
+            }
+            (IpAddr::V6(group), MulticastInterface::Any) => socket.join_multicast_v6(&group, 0)?,
+            (IpAddr::V6(group), MulticastInterface::V6(index)) => {
+                socket.join_multicast_v6(&group, index)?
+            }
+            _ => return Err(mismatched_interface().into()),
+        }
+        Ok(())
+    }
+
+    /// Leave a multicast group that was joined with [`join_multicast`](Self::join_multicast)
+    pub fn leave_multicast(
+        &self,
+        group: IpAddr,
+        interface: MulticastInterface,
+    ) -> Result<(), UdpSocketError> {
+        let socket = &self.outbound.socket;
+        match (multicast_group(group)?, interface) {
+            (IpAddr::V4(group), MulticastInterface::Any) => {
+                socket.leave_multicast_v4(group, Ipv4Addr::UNSPECIFIED)?
+            }
+            (IpAddr::V4(group), MulticastInterface::V4(interface)) => {
+                socket.leave_multicast_v4(group, interface)?
+            }
+            (IpAddr::V6(group), MulticastInterface::Any) => socket.leave_multicast_v6(&group, 0)?,
+            (IpAddr::V6(group), MulticastInterface::V6(index)) => {
+                socket.leave_multicast_v6(&group, index)?
+            }
+            _ => return Err(mismatched_interface().into()),
+        }
+        Ok(())
+    }
+
+    /// Set how many hops values written to a multicast group may travel
+    ///
+    /// This sets the TTL on IPv4 sockets and the hop limit on IPv6 sockets. The default of 1
+    /// keeps multicast traffic on the local network.
+    pub fn set_multicast_ttl(&self, ttl: u32) -> Result<(), UdpSocketError> {
+        let socket = &self.outbound.socket;
+        if self.local_addr()?.is_ipv4() {
+            socket.set_multicast_ttl_v4(ttl)?;
+        } else {
+            SockRef::from(socket.as_ref()).set_multicast_hops_v6(ttl)?;
+        }
+        Ok(())
+    }
+
+    /// Get how many hops values written to a multicast group may travel
+    pub fn multicast_ttl(&self) -> Result<u32, UdpSocketError> {
+        let socket = &self.outbound.socket;
+        if self.local_addr()?.is_ipv4() {
+            Ok(socket.multicast_ttl_v4()?)
+        } else {
+            Ok(SockRef::from(socket.as_ref()).multicast_hops_v6()?)
+        }
+    }
+
+    /// Set whether values written to a multicast group are also delivered to the local host
+    pub fn set_multicast_loop(&self, on: bool) -> Result<(), UdpSocketError> {
+        let socket = &self.outbound.socket;
+        if self.local_addr()?.is_ipv4() {
+            socket.set_multicast_loop_v4(on)?;
+        } else {
+            socket.set_multicast_loop_v6(on)?;
+        }
+        Ok(())
+    }
+
+    /// Get whether values written to a multicast group are also delivered to the local host
+    pub fn multicast_loop(&self) -> Result<bool, UdpSocketError> {
+        let socket = &self.outbound.socket;
+        if self.local_addr()?.is_ipv4() {
+            Ok(socket.multicast_loop_v4()?)
+        } else {
+            Ok(socket.multicast_loop_v6()?)
+        }
+    }
+
+    /// Write a serializable value to a multicast group
+    ///
+    /// This behaves like [`write`](Self::write), but returns
+    /// [`UdpSocketError::NotMulticast`] if `group` isn't a multicast address.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use sockit::UdpSocket;
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    ///   let mut socket = UdpSocket::bind("0.0.0.0:0").await?;
+    ///   socket.set_multicast_ttl(2)?;
+    ///   socket.write_multicast(&"Hello World!", "239.255.0.1:9090".parse()?).await?;
+    ///   Ok(())
+    /// }
+    /// ```
+    pub async fn write_multicast<T: Serialize>(
+        &mut self,
+        value: &T,
+        group: SocketAddr,
+    ) -> Result<(), UdpSocketError> {
+        multicast_group(group.ip())?;
+        self.write(value, group).await
+    }
+}
+
+fn multicast_group(group: IpAddr) -> Result<IpAddr, UdpSocketError> {
+    if group.is_multicast() {
+        Ok(group)
+    } else {
+        Err(UdpSocketError::NotMulticast { addr: group })
+    }
+}
+
+fn mismatched_interface() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidInput,
+        "the multicast interface doesn't match the group's address family",
+    )
+}
@@ -0,0 +1,640 @@
+use crate::encryption::{assert_replay_window, RejectPolicy};
+use crate::{random_u64, Reliability, UdpSocketError};
+use snow::params::{DHChoice, NoiseParams};
+use snow::resolvers::{CryptoResolver, DefaultResolver};
+use snow::{Builder, HandshakeState, StatelessTransportState};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+const PATTERN: &str = "Noise_IK_25519_ChaChaPoly_BLAKE2s";
+const PROLOGUE: &[u8] = b"sockit";
+
+const INITIATION: u8 = 1;
+const RESPONSE: u8 = 2;
+const DATA: u8 = 3;
+
+const TAG_LEN: usize = 16;
+/// The largest handshake message of the IK pattern, with an empty payload
+const MAX_HANDSHAKE_LEN: usize = 96;
+/// The payload of an initiation, the time it was created in nanoseconds since the Unix epoch
+const TIMESTAMP_LEN: usize = 8;
+/// How many rekey intervals a peer may go without a new session before it's forgotten
+const IDLE_AFTER_REKEYS: u32 = 3;
+
+/// The number of bytes a session adds to every datagram: kind, receiver index, nonce and tag
+pub(crate) const OVERHEAD: usize = 1 + 4 + 8 + TAG_LEN;
+
+/// The number of datagrams kept per peer while a handshake is in progress
+const MAX_QUEUED: usize = 256;
+
+/// A static Curve25519 keypair identifying a peer in [`Noise`] handshakes
+#[derive(Clone, PartialEq, Eq)]
+pub struct NoiseKeypair {
+    private: [u8; 32],
+    public: [u8; 32],
+}
+
+impl NoiseKeypair {
+    /// Generate a new random keypair
+    pub fn generate() -> Self {
+        let keypair = Builder::new(params())
+            .generate_keypair()
+            .expect("the default resolver supports Curve25519");
+        let mut private = [0; 32];
+        private.copy_from_slice(&keypair.private);
+        Self::from_private_key(private)
+    }
+
+    /// Restore the keypair with the given private key
+    pub fn from_private_key(private: [u8; 32]) -> Self {
+        let mut dh = DefaultResolver
+            .resolve_dh(&DHChoice::Curve25519)
+            .expect("the default resolver supports Curve25519");
+        dh.set(&private);
+        let mut public = [0; 32];
+        public.copy_from_slice(dh.pubkey());
+        Self { private, public }
+    }
+
+    /// Get the private key, which must be kept secret
+    pub fn private_key(&self) -> [u8; 32] {
+        self.private
+    }
+
+    /// Get the public key, which peers use to authenticate this one
+    pub fn public_key(&self) -> [u8; 32] {
+        self.public
+    }
+}
+
+impl fmt::Debug for NoiseKeypair {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Never print the private key
+        f.debug_struct("NoiseKeypair")
+            .field("public", &self.public)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Encryption with per-peer session keys negotiated by a Noise handshake
+///
+/// Every peer has a static [`NoiseKeypair`]. The first value written to a peer starts a
+/// `Noise_IK_25519_ChaChaPoly_BLAKE2s` handshake, which requires knowing the peer's public key
+/// in advance, see [`peer`](Self::peer). Values written while the handshake is in progress are
+/// queued and sent once it completes. The handshake is retransmitted until it's answered; if it
+/// never is, the queued values are dropped, just like datagrams lost by the network.
+///
+/// A peer only accepts handshakes from public keys it trusts. Like in WireGuard, every handshake
+/// initiation carries an encrypted timestamp, and initiations that aren't newer than the last
+/// one accepted from the same key are rejected as replays. Sessions are replaced by a new
+/// handshake after a number of datagrams or a period of time, and datagrams are checked for
+/// replays like with [`Encryption`](crate::Encryption). Peers that go three rekey intervals
+/// without a new session are forgotten, and must handshake again to exchange values.
+///
+/// Handshake responses are processed by [`read`](crate::UdpSocket::read), so a socket must be
+/// read from for the handshakes it starts to complete.
+///
+/// # Example
+///
+/// ```no_run
+/// use sockit::{Noise, NoiseKeypair, UdpSocket};
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+///   let keypair = NoiseKeypair::generate();
+///   let server_key = [0; 32]; // The server's public key, distributed out of band
+///
+///   let mut socket = UdpSocket::bind("127.0.0.1:0").await?;
+///   socket.set_noise(Noise::new(keypair).peer("127.0.0.1:9090".parse()?, server_key));
+///   socket.write(&"Hello World!", "127.0.0.1:9090".parse()?).await?;
+///   let (reply, from) = socket.read::<String>().await?;
+///   Ok(())
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct Noise {
+    keypair: NoiseKeypair,
+    peers: HashMap<SocketAddr, [u8; 32]>,
+    trusted: HashSet<[u8; 32]>,
+    rekey_after_messages: u64,
+    rekey_after: Duration,
+    retransmission: Reliability,
+    replay_window: usize,
+    on_failure: RejectPolicy,
+    on_replay: RejectPolicy,
+}
+
+impl Noise {
+    /// The default number of datagrams sent in a session before it's replaced
+    pub const DEFAULT_REKEY_AFTER_MESSAGES: u64 = 1 << 32;
+    /// The default age of a session after which it's replaced, 2 minutes
+    pub const DEFAULT_REKEY_AFTER: Duration = Duration::from_secs(120);
+
+    /// Negotiate sessions using this socket's static keypair
+    pub fn new(keypair: NoiseKeypair) -> Self {
+        Self {
+            keypair,
+            peers: HashMap::new(),
+            trusted: HashSet::new(),
+            rekey_after_messages: Self::DEFAULT_REKEY_AFTER_MESSAGES,
+            rekey_after: Self::DEFAULT_REKEY_AFTER,
+            retransmission: Reliability::new(),
+            replay_window: crate::Encryption::DEFAULT_REPLAY_WINDOW,
+            on_failure: RejectPolicy::default(),
+            on_replay: RejectPolicy::default(),
+        }
+    }
+
+    /// Add the peer at `addr` with the given public key, so values can be written to it
+    ///
+    /// The key is also trusted to start handshakes with this socket.
+    pub fn peer(mut self, addr: SocketAddr, public_key: [u8; 32]) -> Self {
+        self.peers.insert(addr, public_key);
+        self.trusted.insert(public_key);
+        self
+    }
+
+    /// Accept handshakes from the peer with this public key, from any address
+    ///
+    /// Once a trusted peer has completed a handshake, values can be written back to it.
+    pub fn trust(mut self, public_key: [u8; 32]) -> Self {
+        self.trusted.insert(public_key);
+        self
+    }
+
+    /// Set how many datagrams are sent in a session before a new handshake replaces it
+    pub fn rekey_after_messages(mut self, messages: u64) -> Self {
+        self.rekey_after_messages = messages.max(1);
+        self
+    }
+
+    /// Set how old a session may get before a new handshake replaces it
+    ///
+    /// Peers without a session younger than three times this age are forgotten.
+    pub fn rekey_after(mut self, age: Duration) -> Self {
+        self.rekey_after = age;
+        self
+    }
+
+    /// Set how handshakes are retransmitted until they are answered
+    pub fn retransmission(mut self, retransmission: Reliability) -> Self {
+        self.retransmission = retransmission;
+        self
+    }
+
+    /// Set how many recent datagrams from each session are checked for replays
+    ///
+    /// # Panics
+    ///
+    /// Panics if `size` is zero or larger than
+    /// [`Encryption::MAX_REPLAY_WINDOW`](crate::Encryption::MAX_REPLAY_WINDOW).
+    pub fn replay_window(mut self, size: usize) -> Self {
+        assert_replay_window(size);
+        self.replay_window = size;
+        self
+    }
+
+    /// Set what happens to datagrams and handshakes that fail authentication or come from an
+    /// untrusted peer
+    pub fn on_failure(mut self, policy: RejectPolicy) -> Self {
+        self.on_failure = policy;
+        self
+    }
+
+    /// Set what happens to replayed datagrams and handshake initiations
+    pub fn on_replay(mut self, policy: RejectPolicy) -> Self {
+        self.on_replay = policy;
+        self
+    }
+
+    /// Get this socket's static public key
+    pub fn public_key(&self) -> [u8; 32] {
+        self.keypair.public
+    }
+
+    pub(crate) fn failure_policy(&self) -> RejectPolicy {
+        self.on_failure
+    }
+
+    pub(crate) fn replay_policy(&self) -> RejectPolicy {
+        self.on_replay
+    }
+
+    pub(crate) fn replay_window_size(&self) -> usize {
+        self.replay_window
+    }
+
+    fn is_trusted(&self, public_key: &[u8]) -> bool {
+        public_key
+            .try_into()
+            .map_or(false, |key: [u8; 32]| self.trusted.contains(&key))
+    }
+}
+
+fn params() -> NoiseParams {
+    PATTERN.parse().expect("the Noise pattern is valid")
+}
+
+/// An established session with a peer
+struct Session {
+    local_index: u32,
+    remote_index: u32,
+    remote_key: [u8; 32],
+    transport: StatelessTransportState,
+    sent: u64,
+    established: Instant,
+}
+
+impl Session {
+    fn seal(&mut self, datagram: &[u8]) -> Vec<u8> {
+        let nonce = self.sent;
+        self.sent += 1;
+        let mut sealed = vec![0; 13 + datagram.len() + TAG_LEN];
+        sealed[0] = DATA;
+        sealed[1..5].copy_from_slice(&self.remote_index.to_be_bytes());
+        sealed[5..13].copy_from_slice(&nonce.to_be_bytes());
+        self.transport
+            .write_message(nonce, datagram, &mut sealed[13..])
+            .expect("datagrams are within the Noise message length limit");
+        sealed
+    }
+}
+
+/// A handshake started by this socket that hasn't been answered yet
+struct Pending {
+    local_index: u32,
+    remote_key: [u8; 32],
+    handshake: HandshakeState,
+    queued: Vec<Vec<u8>>,
+}
+
+/// The sessions with a single peer
+#[derive(Default)]
+struct Peer {
+    /// The session used to send datagrams
+    current: Option<Session>,
+    /// A session this socket answered a handshake for, used once the peer sends on it
+    next: Option<Session>,
+    /// The session that was replaced last, kept to receive datagrams still in flight
+    previous: Option<Session>,
+    pending: Option<Pending>,
+    /// The last handshake initiation answered and its response, to answer retransmissions
+    answered: Option<(Vec<u8>, Vec<u8>)>,
+}
+
+impl Peer {
+    /// Whether the peer has neither a handshake in progress nor a session younger than `max_age`
+    fn is_idle(&self, max_age: Duration) -> bool {
+        self.pending.is_none()
+            && [&self.current, &self.next, &self.previous]
+                .into_iter()
+                .flatten()
+                .all(|session| session.established.elapsed() >= max_age)
+    }
+
+    fn session_mut(&mut self, local_index: u32) -> Option<&mut Session> {
+        [&mut self.current, &mut self.next, &mut self.previous]
+            .into_iter()
+            .flatten()
+            .find(|session| session.local_index == local_index)
+    }
+
+    fn replace_current(&mut self, session: Session) {
+        self.previous = self.current.replace(session);
+    }
+}
+
+/// What to send after sealing a datagram
+pub(crate) struct Outgoing {
+    /// The sealed datagram, unless it was queued until a handshake completes
+    pub(crate) datagram: Option<Vec<u8>>,
+    /// A handshake initiation to send, and the index identifying it
+    pub(crate) handshake: Option<(u32, Vec<u8>)>,
+}
+
+/// What a received datagram turned out to be
+pub(crate) enum Incoming {
+    /// A decrypted datagram from the session with the given local index
+    Data {
+        session: u32,
+        nonce: u64,
+        plaintext: Vec<u8>,
+    },
+    /// A handshake message, answered by these datagrams to the sender
+    Handshake(Vec<Vec<u8>>),
+}
+
+/// The Noise sessions of a socket, shared by its sending and receiving sides
+pub(crate) struct NoiseSessions {
+    pub(crate) config: Noise,
+    peers: Mutex<HashMap<SocketAddr, Peer>>,
+    /// The timestamp of the last initiation accepted from each trusted public key
+    initiations: Mutex<HashMap<[u8; 32], u64>>,
+    /// When idle peers were last forgotten
+    swept: Mutex<Instant>,
+    next_index: AtomicU32,
+}
+
+impl NoiseSessions {
+    pub(crate) fn new(config: Noise) -> Self {
+        Self {
+            config,
+            peers: Mutex::default(),
+            initiations: Mutex::default(),
+            swept: Mutex::new(Instant::now()),
+            next_index: AtomicU32::new(random_u64() as u32),
+        }
+    }
+
+    /// Forget the peers that went idle, at most once per rekey interval
+    fn sweep(&self, peers: &mut HashMap<SocketAddr, Peer>) {
+        let mut swept = self.swept.lock().unwrap();
+        if swept.elapsed() < self.config.rekey_after {
+            return;
+        }
+        *swept = Instant::now();
+        let max_age = self.config.rekey_after.saturating_mul(IDLE_AFTER_REKEYS);
+        peers.retain(|_, peer| !peer.is_idle(max_age));
+    }
+
+    /// Encrypt a datagram for `to`, starting a handshake if there is no session to use
+    pub(crate) fn seal(&self, to: SocketAddr, datagram: &[u8]) -> Result<Outgoing, UdpSocketError> {
+        let mut peers = self.peers.lock().unwrap();
+        self.sweep(&mut peers);
+        let peer = peers.entry(to).or_default();
+
+        if let Some(session) = &mut peer.current {
+            let expired = session.sent >= self.config.rekey_after_messages
+                || session.established.elapsed() >= self.config.rekey_after;
+            let remote_key = session.remote_key;
+            let sealed = session.seal(datagram);
+            let handshake = match peer.pending {
+                None if expired => Some(self.initiate(peer, remote_key)),
+                _ => None,
+            };
+            return Ok(Outgoing {
+                datagram: Some(sealed),
+                handshake,
+            });
+        }
+
+        let handshake = match peer.pending {
+            Some(_) => None,
+            None => {
+                let remote_key = *self
+                    .config
+                    .peers
+                    .get(&to)
+                    .ok_or(UdpSocketError::UnknownPeer { addr: to })?;
+                Some(self.initiate(peer, remote_key))
+            }
+        };
+        let pending = peer.pending.as_mut().expect("a handshake is in progress");
+        if pending.queued.len() == MAX_QUEUED {
+            pending.queued.remove(0);
+        }
+        pending.queued.push(datagram.to_vec());
+        Ok(Outgoing {
+            datagram: None,
+            handshake,
+        })
+    }
+
+    /// Start a handshake with `peer`, returning its index and initiation message
+    fn initiate(&self, peer: &mut Peer, remote_key: [u8; 32]) -> (u32, Vec<u8>) {
+        let mut handshake = Builder::new(params())
+            .local_private_key(&self.config.keypair.private)
+            .remote_public_key(&remote_key)
+            .prologue(PROLOGUE)
+            .build_initiator()
+            .expect("the handshake parameters are valid");
+        let local_index = self.next_index.fetch_add(1, Ordering::Relaxed);
+
+        let mut message = vec![0; 5 + MAX_HANDSHAKE_LEN + TIMESTAMP_LEN];
+        message[0] = INITIATION;
+        message[1..5].copy_from_slice(&local_index.to_be_bytes());
+        let timestamp = crate::unique_timestamp().to_be_bytes();
+        let len = handshake
+            .write_message(&timestamp, &mut message[5..])
+            .expect("the handshake message fits");
+        message.truncate(5 + len);
+
+        peer.pending = Some(Pending {
+            local_index,
+            remote_key,
+            handshake,
+            queued: Vec::new(),
+        });
+        (local_index, message)
+    }
+
+    /// Handle a datagram received from `from`, returning `None` if there is nothing to deliver
+    /// or answer
+    pub(crate) fn receive(
+        &self,
+        from: SocketAddr,
+        datagram: &[u8],
+    ) -> Result<Option<Incoming>, UdpSocketError> {
+        let failed = || UdpSocketError::AuthenticationFailed { from };
+        let index = |at: usize| {
+            datagram
+                .get(at..at + 4)
+                .map(|bytes| u32::from_be_bytes(bytes.try_into().unwrap()))
+                .ok_or_else(failed)
+        };
+        let mut peers = self.peers.lock().unwrap();
+        self.sweep(&mut peers);
+
+        match datagram.first() {
+            Some(&INITIATION) => {
+                if let Some((initiation, response)) =
+                    peers.get(&from).and_then(|peer| peer.answered.as_ref())
+                {
+                    if initiation == datagram {
+                        return Ok(Some(Incoming::Handshake(vec![response.clone()])));
+                    }
+                }
+                let (session, response) = self.respond(from, index(1)?, &datagram[5..])?;
+                let peer = peers.entry(from).or_default();
+                match peer.current {
+                    Some(_) => peer.next = Some(session),
+                    None => peer.current = Some(session),
+                }
+                peer.answered = Some((datagram.to_vec(), response.clone()));
+                Ok(Some(Incoming::Handshake(vec![response])))
+            }
+            Some(&RESPONSE) => {
+                let local_index = index(1)?;
+                let remote_index = index(5)?;
+                let peer = match peers.get_mut(&from) {
+                    Some(peer) => peer,
+                    None => return Ok(None),
+                };
+                let pending = match peer.pending.take() {
+                    Some(pending) if pending.local_index == local_index => pending,
+                    // A retransmitted response to a handshake that has already completed
+                    other => {
+                        peer.pending = other;
+                        return Ok(None);
+                    }
+                };
+                let mut handshake = pending.handshake;
+                handshake
+                    .read_message(&datagram[9..], &mut [0; MAX_HANDSHAKE_LEN])
+                    .map_err(|_| failed())?;
+                let mut session = Session {
+                    local_index,
+                    remote_index,
+                    remote_key: pending.remote_key,
+                    transport: handshake
+                        .into_stateless_transport_mode()
+                        .expect("the handshake is finished"),
+                    sent: 0,
+                    established: Instant::now(),
+                };
+                let flushed = pending
+                    .queued
+                    .iter()
+                    .map(|datagram| session.seal(datagram))
+                    .collect();
+                peer.replace_current(session);
+                Ok(Some(Incoming::Handshake(flushed)))
+            }
+            Some(&DATA) if datagram.len() >= 13 + TAG_LEN => {
+                let local_index = index(1)?;
+                let nonce = u64::from_be_bytes(datagram[5..13].try_into().unwrap());
+                // A session that was replaced long ago, or one whose handshake response hasn't
+                // arrived yet
+                let peer = match peers.get_mut(&from) {
+                    Some(peer) => peer,
+                    None => return Ok(None),
+                };
+                let promote = matches!(&peer.next, Some(next) if next.local_index == local_index);
+                let session = match peer.session_mut(local_index) {
+                    Some(session) => session,
+                    None => return Ok(None),
+                };
+                let mut plaintext = vec![0; datagram.len() - 13 - TAG_LEN];
+                session
+                    .transport
+                    .read_message(nonce, &datagram[13..], &mut plaintext)
+                    .map_err(|_| failed())?;
+                // The peer has received the response to its handshake and moved to the session
+                if promote {
+                    let next = peer.next.take().expect("the next session exists");
+                    peer.replace_current(next);
+                }
+                Ok(Some(Incoming::Data {
+                    session: local_index,
+                    nonce,
+                    plaintext,
+                }))
+            }
+            _ => Err(failed()),
+        }
+    }
+
+    /// Answer a handshake initiation, returning the new session and the response message
+    fn respond(
+        &self,
+        from: SocketAddr,
+        remote_index: u32,
+        message: &[u8],
+    ) -> Result<(Session, Vec<u8>), UdpSocketError> {
+        let mut handshake = Builder::new(params())
+            .local_private_key(&self.config.keypair.private)
+            .prologue(PROLOGUE)
+            .build_responder()
+            .expect("the handshake parameters are valid");
+        let mut payload = [0; MAX_HANDSHAKE_LEN];
+        let len = handshake
+            .read_message(message, &mut payload)
+            .map_err(|_| UdpSocketError::AuthenticationFailed { from })?;
+        let timestamp: [u8; TIMESTAMP_LEN] = payload[..len]
+            .try_into()
+            .map_err(|_| UdpSocketError::AuthenticationFailed { from })?;
+        let timestamp = u64::from_be_bytes(timestamp);
+
+        let remote_key = handshake.get_remote_static().unwrap_or_default();
+        if !self.config.is_trusted(remote_key) {
+            return Err(UdpSocketError::UntrustedPeer { from });
+        }
+        let remote_key: [u8; 32] = remote_key.try_into().unwrap();
+
+        // The timestamp is authenticated by the initiator's static key, so an initiation that
+        // isn't newer than the last one accepted from that key was replayed
+        let mut initiations = self.initiations.lock().unwrap();
+        let latest = initiations.entry(remote_key).or_default();
+        if timestamp <= *latest {
+            return Err(UdpSocketError::ReplayDetected { from });
+        }
+        *latest = timestamp;
+        drop(initiations);
+
+        let local_index = self.next_index.fetch_add(1, Ordering::Relaxed);
+        let mut response = vec![0; 9 + MAX_HANDSHAKE_LEN];
+        response[0] = RESPONSE;
+        response[1..5].copy_from_slice(&remote_index.to_be_bytes());
+        response[5..9].copy_from_slice(&local_index.to_be_bytes());
+        let len = handshake
+            .write_message(&[], &mut response[9..])
+            .expect("the handshake message fits");
+        response.truncate(9 + len);
+
+        let session = Session {
+            local_index,
+            remote_index,
+            remote_key,
+            transport: handshake
+                .into_stateless_transport_mode()
+                .expect("the handshake is finished"),
+            sent: 0,
+            established: Instant::now(),
+        };
+        Ok((session, response))
+    }
+
+    /// Retransmit a handshake initiation in the background until it's answered, dropping the
+    /// values queued behind it if it never is
+    pub(crate) fn retransmit(
+        self: Arc<Self>,
+        socket: Arc<tokio::net::UdpSocket>,
+        to: SocketAddr,
+        local_index: u32,
+        message: Vec<u8>,
+    ) {
+        let retransmission = self.config.retransmission;
+        tokio::spawn(async move {
+            let mut rto = retransmission.initial_rto;
+            for attempt in 1..=retransmission.max_attempts {
+                tokio::time::sleep(rto).await;
+                if !self.still_pending(to, local_index, attempt == retransmission.max_attempts) {
+                    return;
+                }
+                let _ = socket.send_to(&message, to).await;
+                rto = (rto * 2).min(retransmission.max_rto);
+            }
+        });
+    }
+
+    /// Check whether the handshake with the given index is still waiting for a response,
+    /// abandoning it if it is but this was the last attempt
+    fn still_pending(&self, to: SocketAddr, local_index: u32, last_attempt: bool) -> bool {
+        let mut peers = self.peers.lock().unwrap();
+        let peer = match peers.get_mut(&to) {
+            Some(peer) => peer,
+            None => return false,
+        };
+        let in_progress =
+            matches!(&peer.pending, Some(pending) if pending.local_index == local_index);
+        if in_progress && last_attempt {
+            peer.pending = None;
+            return false;
+        }
+        in_progress
+    }
+}
@@ -0,0 +1,591 @@
+//! The send and receive paths shared by [`UdpSocket`](crate::UdpSocket) and its halves
+//!
+//! Values are encoded by the socket's codec into a frame. [`Outbound`] turns frames into
+//! datagrams and [`Inbound`] turns datagrams back into frames.
+use crate::checksum::Checksum;
+use crate::compression::Compression;
+use crate::encryption::{self, Encryption, RejectPolicy, Sealer};
+use crate::envelope::{self, Envelope};
+use crate::fingerprint;
+use crate::fragment::{self, Reassembler};
+use crate::noise;
+use crate::noise::{Incoming, NoiseSessions};
+use crate::registry::{self, MessageTags};
+use crate::replay::ReplayWindows;
+use crate::sequence::{self, SequenceCounters, Sequencer};
+use crate::signing::{self, Signing};
+use crate::{Codec, Fragmentation, Sequencing, UdpSocketError, MAX_DATAGRAM_SIZE};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::ops::Range;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+
+/// A received frame, its sender and the public key that signed it, if signatures are verified
+pub(crate) type SignedFrame = (Vec<u8>, SocketAddr, Option<[u8; 32]>);
+
+/// The sending side of a socket, which can be cloned and shared between tasks
+#[derive(Clone)]
+pub(crate) struct Outbound {
+    pub(crate) socket: Arc<tokio::net::UdpSocket>,
+    pub(crate) max_datagram_size: usize,
+    pub(crate) peer_capacity: usize,
+    pub(crate) fragmentation: bool,
+    pub(crate) sequencing: bool,
+    pub(crate) compression: Option<Compression>,
+    pub(crate) encryption: Option<Sealer>,
+    pub(crate) noise: Option<Arc<NoiseSessions>>,
+    pub(crate) signing: Option<Arc<Signing>>,
+    pub(crate) checksum: Option<Checksum>,
+    pub(crate) envelope: Option<Envelope>,
+    pub(crate) fingerprinting: bool,
+    pub(crate) tags: Option<Arc<MessageTags>>,
+    next_message_id: Arc<AtomicU32>,
+    sequences: Arc<SequenceCounters>,
+}
+
+impl Outbound {
+    pub(crate) fn new(socket: Arc<tokio::net::UdpSocket>, peer_capacity: usize) -> Self {
+        Self {
+            socket,
+            max_datagram_size: MAX_DATAGRAM_SIZE,
+            peer_capacity,
+            fragmentation: false,
+            sequencing: false,
+            compression: None,
+            encryption: None,
+            noise: None,
+            signing: None,
+            checksum: None,
+            envelope: None,
+            fingerprinting: false,
+            tags: None,
+            next_message_id: Arc::default(),
+            sequences: Arc::default(),
+        }
+    }
+
+    /// The size of the largest datagram that may be sent
+    pub(crate) fn send_limit(&self) -> usize {
+        self.max_datagram_size.min(self.peer_capacity)
+    }
+
+    /// The size of the largest datagram that fits within the send limit once it is encrypted
+    fn datagram_budget(&self) -> usize {
+        self.send_limit().saturating_sub(self.overhead())
+    }
+
+    /// The number of bytes encryption and checksums add to every datagram
+    fn overhead(&self) -> usize {
+        let encryption = match (&self.encryption, &self.noise) {
+            (Some(_), _) => encryption::OVERHEAD,
+            (_, Some(_)) => noise::OVERHEAD,
+            (None, None) => 0,
+        };
+        encryption + self.checksum.map_or(0, Checksum::size)
+    }
+
+    /// The size of the largest encoded value that can be sent under the current configuration
+    pub(crate) fn value_limit(&self) -> usize {
+        let frame_limit = match self.fragmentation {
+            true => self
+                .datagram_budget()
+                .saturating_sub(fragment::HEADER_LEN)
+                .saturating_mul(u16::MAX as usize),
+            false => self.datagram_budget(),
+        };
+        // Frames that don't compress well are sent as they are behind a one-byte flag
+        let compression = self.compression.map_or(0, |_| 1);
+        let framing = [
+            (self.sequencing, sequence::HEADER_LEN),
+            (self.envelope.is_some(), envelope::HEADER_LEN),
+            (self.tags.is_some(), registry::TAG_LEN),
+            (self.fingerprinting, fingerprint::LEN),
+            (
+                self.signing
+                    .as_ref()
+                    .map_or(false, |s| s.public_key().is_some()),
+                signing::TRAILER_LEN,
+            ),
+        ]
+        .into_iter()
+        .filter(|&(enabled, _)| enabled)
+        .map(|(_, len)| len)
+        .sum::<usize>();
+        frame_limit.saturating_sub(compression + framing)
+    }
+
+    /// Encode a value sent to `to` into a frame, stamping it with a sequence number, encoding it
+    /// with [`encode_value`](Self::encode_value) and signing the whole frame if enabled
+    pub(crate) fn value_frame<C: Codec, T: Serialize>(
+        &self,
+        codec: &C,
+        value: &T,
+        to: SocketAddr,
+    ) -> Result<Vec<u8>, UdpSocketError> {
+        let mut frame = Vec::new();
+        if self.sequencing {
+            self.sequences.stamp(to, &mut frame);
+        }
+        self.encode_value(codec, value, &mut frame)?;
+        self.sign(&mut frame);
+        Ok(frame)
+    }
+
+    /// Append an encoded value to a frame, wrapping it in an envelope, tagging it with its type
+    /// and fingerprinting its type if enabled
+    pub(crate) fn encode_value<C: Codec, T: Serialize>(
+        &self,
+        codec: &C,
+        value: &T,
+        frame: &mut Vec<u8>,
+    ) -> Result<(), UdpSocketError> {
+        if let Some(envelope) = &self.envelope {
+            envelope.wrap(frame);
+        }
+        if let Some(tags) = &self.tags {
+            tags.stamp::<T>(frame)?;
+        }
+        let tags = self.tags.as_deref();
+        if self.fingerprinting {
+            match tags.and_then(MessageTags::fingerprint_of::<T>) {
+                Some(fingerprint) => frame.extend_from_slice(&fingerprint.to_be_bytes()),
+                // Unregistered types are traced while they are encoded and have no size limit
+                None => return fingerprint::encode(codec, value, frame),
+            }
+        }
+        let value_start = frame.len();
+        codec.encode(value, frame)?;
+        // Declared sizes count bincode's bytes, which other codecs don't stick to
+        let limit = tags
+            .filter(|_| codec.honors_max_encoded_size())
+            .and_then(MessageTags::max_size_of::<T>);
+        if let Some(limit) = limit {
+            let size = frame.len() - value_start;
+            if size > limit {
+                return Err(UdpSocketError::MessageTooLarge { size, limit });
+            }
+        }
+        Ok(())
+    }
+
+    /// Sign a frame if signing is enabled
+    pub(crate) fn sign(&self, frame: &mut Vec<u8>) {
+        if let Some(signing) = &self.signing {
+            signing.sign(frame);
+        }
+    }
+
+    /// Send an encoded message, compressing it and splitting it into fragments if enabled
+    pub(crate) async fn send_frame(
+        &self,
+        frame: &[u8],
+        send_to: SocketAddr,
+    ) -> Result<(), UdpSocketError> {
+        let compressed;
+        let frame = match &self.compression {
+            Some(compression) => {
+                compressed = compression.compress(frame)?;
+                &compressed
+            }
+            None => frame,
+        };
+
+        if !self.fragmentation {
+            return self.send_datagram(frame, send_to).await;
+        }
+        let message_id = self.next_message_id.fetch_add(1, Ordering::Relaxed);
+        for datagram in fragment::split(frame, message_id, self.datagram_budget())? {
+            self.send_datagram(&datagram, send_to).await?;
+        }
+        Ok(())
+    }
+
+    /// Send a single datagram, encrypting it if enabled and checking it against the send limit
+    async fn send_datagram(
+        &self,
+        datagram: &[u8],
+        send_to: SocketAddr,
+    ) -> Result<(), UdpSocketError> {
+        let limit = self.send_limit();
+        let size = datagram.len() + self.overhead();
+        if size > limit {
+            return Err(UdpSocketError::MessageTooLarge { size, limit });
+        }
+        let limited_broadcast = send_to.ip() == IpAddr::V4(Ipv4Addr::BROADCAST);
+        if limited_broadcast && !self.socket.broadcast()? {
+            return Err(UdpSocketError::BroadcastDisabled { addr: send_to });
+        }
+
+        if let Some(sealer) = &self.encryption {
+            return self.transmit(&sealer.seal(datagram), send_to).await;
+        }
+        let noise = match &self.noise {
+            Some(noise) => noise,
+            None => return self.transmit(datagram, send_to).await,
+        };
+        let outgoing = noise.seal(send_to, datagram)?;
+        if let Some((index, initiation)) = outgoing.handshake {
+            let initiation = with_checksum(self.checksum, initiation);
+            self.send_raw(&initiation, send_to).await?;
+            noise
+                .clone()
+                .retransmit(self.socket.clone(), send_to, index, initiation);
+        }
+        match outgoing.datagram {
+            Some(sealed) => self.transmit(&sealed, send_to).await,
+            None => Ok(()),
+        }
+    }
+
+    /// Send a datagram after appending a checksum if enabled
+    async fn transmit(&self, datagram: &[u8], send_to: SocketAddr) -> Result<(), UdpSocketError> {
+        match self.checksum {
+            Some(checksum) => {
+                let mut datagram = datagram.to_vec();
+                checksum.append(&mut datagram);
+                self.send_raw(&datagram, send_to).await
+            }
+            None => self.send_raw(datagram, send_to).await,
+        }
+    }
+
+    /// Send bytes that are ready to go on the wire
+    async fn send_raw(&self, datagram: &[u8], send_to: SocketAddr) -> Result<(), UdpSocketError> {
+        match self.socket.send_to(datagram, send_to).await {
+            Ok(_) => Ok(()),
+            // Sending to a subnet broadcast address without `SO_BROADCAST` is refused by the OS
+            Err(e)
+                if e.kind() == io::ErrorKind::PermissionDenied
+                    && may_be_broadcast(send_to.ip())
+                    && !self.socket.broadcast()? =>
+            {
+                Err(UdpSocketError::BroadcastDisabled { addr: send_to })
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+/// The receiving side of a socket
+pub(crate) struct Inbound {
+    pub(crate) socket: Arc<tokio::net::UdpSocket>,
+    /// Holds one byte more than the socket's capacity so that oversized datagrams can be detected
+    buffer: Vec<u8>,
+    pub(crate) fragmentation: Option<Fragmentation>,
+    reassembler: Reassembler,
+    pub(crate) sequencing: Option<Sequencing>,
+    sequencer: Sequencer,
+    pub(crate) compression: Option<Compression>,
+    encryption: Option<Encryption>,
+    noise: Option<Arc<NoiseSessions>>,
+    replay: ReplayWindows,
+    pub(crate) signing: Option<Arc<Signing>>,
+    pub(crate) checksum: Option<Checksum>,
+    pub(crate) checks: ValueChecks,
+}
+
+/// The checks of every value read, which can be cloned to decode values away from the socket
+#[derive(Clone, Default)]
+pub(crate) struct ValueChecks {
+    pub(crate) envelope: Option<Envelope>,
+    pub(crate) fingerprinting: bool,
+    pub(crate) tags: Option<Arc<MessageTags>>,
+}
+
+impl ValueChecks {
+    /// Decode a value of type `T` received from `from`, checking its envelope, tag and
+    /// fingerprint if enabled
+    pub(crate) fn decode_value<C: Codec, T: DeserializeOwned>(
+        &self,
+        codec: &C,
+        frame: &[u8],
+        from: SocketAddr,
+    ) -> Result<T, UdpSocketError> {
+        let frame = self.unwrap_envelope(frame, from)?;
+        let value = match &self.tags {
+            Some(tags) => tags.strip::<T>(from, frame)?,
+            None => frame,
+        };
+        if !self.fingerprinting {
+            return codec.decode(value);
+        }
+        match self
+            .tags
+            .as_deref()
+            .and_then(MessageTags::fingerprint_of::<T>)
+        {
+            Some(expected) => codec.decode(fingerprint::check(from, expected, value)?),
+            // Unregistered types are traced while they are decoded
+            None => fingerprint::decode(codec, from, value),
+        }
+    }
+
+    /// Check the envelope of a value received from `from` if enabled, returning the rest of it
+    pub(crate) fn unwrap_envelope<'a>(
+        &self,
+        frame: &'a [u8],
+        from: SocketAddr,
+    ) -> Result<&'a [u8], UdpSocketError> {
+        match &self.envelope {
+            Some(envelope) => envelope.unwrap(from, frame),
+            None => Ok(frame),
+        }
+    }
+}
+
+impl Inbound {
+    pub(crate) fn new(socket: Arc<tokio::net::UdpSocket>, capacity: usize) -> Self {
+        Self {
+            socket,
+            buffer: vec![0; capacity + 1],
+            fragmentation: None,
+            reassembler: Reassembler::default(),
+            sequencing: None,
+            sequencer: Sequencer::default(),
+            compression: None,
+            encryption: None,
+            noise: None,
+            replay: ReplayWindows::default(),
+            signing: None,
+            checksum: None,
+            checks: ValueChecks::default(),
+        }
+    }
+
+    /// Decrypt and authenticate received datagrams, forgetting the replay windows of any
+    /// previous configuration
+    pub(crate) fn set_encryption(&mut self, encryption: Encryption) {
+        self.encryption = Some(encryption);
+        self.noise = None;
+        self.replay = ReplayWindows::default();
+    }
+
+    pub(crate) fn encryption(&self) -> Option<&Encryption> {
+        self.encryption.as_ref()
+    }
+
+    /// Decrypt received datagrams with Noise sessions, forgetting the replay windows of any
+    /// previous configuration
+    pub(crate) fn set_noise(&mut self, noise: Arc<NoiseSessions>) {
+        self.noise = Some(noise);
+        self.encryption = None;
+        self.replay = ReplayWindows::default();
+    }
+
+    pub(crate) fn noise(&self) -> Option<&NoiseSessions> {
+        self.noise.as_deref()
+    }
+
+    /// The size of the largest datagram that can be received
+    pub(crate) fn capacity(&self) -> usize {
+        self.buffer.len() - 1
+    }
+
+    /// Decode a value of type `T` received from `from`, see [`ValueChecks::decode_value`]
+    pub(crate) fn decode_value<C: Codec, T: DeserializeOwned>(
+        &self,
+        codec: &C,
+        frame: &[u8],
+        from: SocketAddr,
+    ) -> Result<T, UdpSocketError> {
+        self.checks.decode_value(codec, frame, from)
+    }
+
+    /// Receive the next frame holding a value
+    pub(crate) async fn recv_value_frame(
+        &mut self,
+    ) -> Result<(Vec<u8>, SocketAddr), UdpSocketError> {
+        let (frame, src, _) = self.recv_signed_value_frame().await?;
+        Ok((frame, src))
+    }
+
+    /// Receive the next frame holding a value and the public key that signed it, if signatures
+    /// are verified, discarding or reordering it if sequencing is enabled
+    ///
+    /// Signatures are verified before the frame is sequenced, so forged frames can't affect the
+    /// sequencing state.
+    pub(crate) async fn recv_signed_value_frame(&mut self) -> Result<SignedFrame, UdpSocketError> {
+        loop {
+            if let Some(ready) = self.sequencer.pop_ready() {
+                return Ok(ready);
+            }
+            let (frame, src) = self.recv_frame().await?;
+            let (frame, src, signer) = match self.verify(frame, src)? {
+                Some(verified) => verified,
+                None => continue,
+            };
+            match self.sequencing {
+                Some(mode) => self.sequencer.accept(mode, src, &frame, signer)?,
+                None => return Ok((frame, src, signer)),
+            }
+        }
+    }
+
+    /// Receive the next frame of a protocol that numbers its own frames, like reliable delivery
+    /// or RPC, verifying its signature if enabled
+    pub(crate) async fn recv_verified_frame(
+        &mut self,
+    ) -> Result<(Vec<u8>, SocketAddr), UdpSocketError> {
+        loop {
+            let (frame, src) = self.recv_frame().await?;
+            if let Some((frame, src, _)) = self.verify(frame, src)? {
+                return Ok((frame, src));
+            }
+        }
+    }
+
+    /// Verify and strip the signature of a frame if signatures are verified, returning `None` if
+    /// the frame is dropped
+    fn verify(
+        &self,
+        mut frame: Vec<u8>,
+        src: SocketAddr,
+    ) -> Result<Option<SignedFrame>, UdpSocketError> {
+        let signing = match self.signing.as_ref().filter(|signing| signing.verifies()) {
+            Some(signing) => signing,
+            None => return Ok(Some((frame, src, None))),
+        };
+        match signing.verify(src, &mut frame) {
+            Ok(signer) => Ok(Some((frame, src, Some(signer)))),
+            Err(_) if signing.failure_policy() == RejectPolicy::Drop => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Receive the next complete encoded message, reassembling and decompressing it if enabled
+    async fn recv_frame(&mut self) -> Result<(Vec<u8>, SocketAddr), UdpSocketError> {
+        let (frame, src) = self.recv_message().await?;
+        match &self.compression {
+            Some(compression) => Ok((compression.decompress(src, &frame)?, src)),
+            None => Ok((frame, src)),
+        }
+    }
+
+    /// Receive the next complete message, reassembling fragments if necessary
+    async fn recv_message(&mut self) -> Result<(Vec<u8>, SocketAddr), UdpSocketError> {
+        loop {
+            let (range, src) = self.recv_datagram().await?;
+            let datagram = &self.buffer[range];
+            match &self.fragmentation {
+                Some(config) => {
+                    if let Some(frame) = self.reassembler.insert(config, src, datagram)? {
+                        return Ok((frame, src));
+                    }
+                }
+                None => return Ok((datagram.to_vec(), src)),
+            }
+        }
+    }
+
+    /// Receive a single datagram into the buffer and decrypt it if enabled, returning the range
+    /// of the buffer holding it and its sender
+    async fn recv_datagram(&mut self) -> Result<(Range<usize>, SocketAddr), UdpSocketError> {
+        loop {
+            let (len, src) = self.socket.recv_from(&mut self.buffer).await?;
+            if len > self.capacity() {
+                return Err(UdpSocketError::Truncated {
+                    received: len,
+                    capacity: self.capacity(),
+                    from: src,
+                });
+            }
+            let len = match self.checksum {
+                Some(checksum) => checksum.verify(src, &self.buffer[..len])?,
+                None => len,
+            };
+            if let Some(noise) = self.noise.clone() {
+                match self.open_noise(&noise, len, src).await? {
+                    Some(plaintext) => return Ok((plaintext, src)),
+                    None => continue,
+                }
+            }
+            let encryption = match &self.encryption {
+                Some(encryption) => encryption,
+                None => return Ok((0..len, src)),
+            };
+            let opened = match encryption.open(src, &mut self.buffer[..len]) {
+                Ok(opened) => opened,
+                Err(_) if encryption.failure_policy() == RejectPolicy::Drop => continue,
+                Err(e) => return Err(e),
+            };
+            let window = encryption.replay_window_size();
+            match self
+                .replay
+                .check(window, src, opened.sender, opened.counter)
+            {
+                Ok(()) => return Ok((opened.plaintext, src)),
+                Err(_) if encryption.replay_policy() == RejectPolicy::Drop => continue,
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// Handle a datagram received with Noise enabled, answering handshakes and returning the
+    /// range of the buffer holding the decrypted datagram, or `None` if there is nothing to read
+    async fn open_noise(
+        &mut self,
+        noise: &NoiseSessions,
+        len: usize,
+        src: SocketAddr,
+    ) -> Result<Option<Range<usize>>, UdpSocketError> {
+        let config = &noise.config;
+        match noise.receive(src, &self.buffer[..len]) {
+            Ok(Some(Incoming::Data {
+                session,
+                nonce,
+                plaintext,
+            })) => {
+                let window = config.replay_window_size();
+                match self.replay.check(window, src, session.into(), nonce) {
+                    Ok(()) => {
+                        self.buffer[..plaintext.len()].copy_from_slice(&plaintext);
+                        Ok(Some(0..plaintext.len()))
+                    }
+                    Err(_) if config.replay_policy() == RejectPolicy::Drop => Ok(None),
+                    Err(e) => Err(e),
+                }
+            }
+            Ok(Some(Incoming::Handshake(replies))) => {
+                for reply in replies {
+                    let reply = with_checksum(self.checksum, reply);
+                    self.socket.send_to(&reply, src).await?;
+                }
+                Ok(None)
+            }
+            Ok(None) => Ok(None),
+            Err(e) => {
+                let policy = match e {
+                    UdpSocketError::ReplayDetected { .. } => config.replay_policy(),
+                    _ => config.failure_policy(),
+                };
+                match policy {
+                    RejectPolicy::Drop => Ok(None),
+                    RejectPolicy::Error => Err(e),
+                }
+            }
+        }
+    }
+}
+
+/// Check whether an address is the limited broadcast address or could be the broadcast address
+/// of a subnet, which has all of its host bits set
+///
+/// The netmasks of the local interfaces aren't known, so any IPv4 address that ends in at least
+/// two set bits counts, which are the broadcast addresses of subnets from /30 down to /8.
+fn may_be_broadcast(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => ip.is_broadcast() || u32::from(ip).trailing_ones() >= 2,
+        IpAddr::V6(_) => false,
+    }
+}
+
+/// Append a checksum to a datagram if enabled
+fn with_checksum(checksum: Option<Checksum>, mut datagram: Vec<u8>) -> Vec<u8> {
+    if let Some(checksum) = checksum {
+        checksum.append(&mut datagram);
+    }
+    datagram
+}
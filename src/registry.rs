@@ -0,0 +1,395 @@
+use crate::fingerprint;
+use crate::pipeline::Inbound;
+use crate::{BincodeCodec, Codec, Message, UdpSocket, UdpSocketError};
+use serde::de::DeserializeOwned;
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::fmt;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+/// The number of bytes the message tag adds to every value
+pub(crate) const TAG_LEN: usize = 4;
+
+/// The tags of the message types in a [`Registry`], shared with the send and receive paths
+#[derive(Debug, Clone, Default)]
+pub(crate) struct MessageTags {
+    by_type: HashMap<TypeId, Entry>,
+}
+
+/// What is known about a registered type, which is more for a [`Message`]
+#[derive(Debug, Clone, Copy)]
+struct Entry {
+    tag: u32,
+    fingerprint: u64,
+    max_size: Option<usize>,
+}
+
+impl MessageTags {
+    fn entry<T: ?Sized>(&self) -> Result<Entry, UdpSocketError> {
+        // Values are written by reference, so `T` may borrow and only its lifetimes are erased
+        self.by_type.get(&typeid::of::<T>()).copied().ok_or(
+            UdpSocketError::UnregisteredMessageType {
+                type_name: std::any::type_name::<T>(),
+            },
+        )
+    }
+
+    /// The tag of type `T`, which must be registered
+    fn tag_of<T: ?Sized>(&self) -> Result<u32, UdpSocketError> {
+        self.entry::<T>().map(|entry| entry.tag)
+    }
+
+    /// The fingerprint of type `T`, if it is registered
+    pub(crate) fn fingerprint_of<T: ?Sized>(&self) -> Option<u64> {
+        self.entry::<T>().ok().map(|entry| entry.fingerprint)
+    }
+
+    /// The declared maximum encoded size of type `T`, if it is a registered [`Message`]
+    pub(crate) fn max_size_of<T: ?Sized>(&self) -> Option<usize> {
+        self.entry::<T>().ok()?.max_size
+    }
+
+    /// Append the tag of type `T` to a frame
+    pub(crate) fn stamp<T: ?Sized>(&self, frame: &mut Vec<u8>) -> Result<(), UdpSocketError> {
+        frame.extend_from_slice(&self.tag_of::<T>()?.to_be_bytes());
+        Ok(())
+    }
+
+    /// Check the tag at the start of a value of type `T` received from `from`, returning the
+    /// value's bytes
+    pub(crate) fn strip<'a, T: ?Sized>(
+        &self,
+        from: SocketAddr,
+        frame: &'a [u8],
+    ) -> Result<&'a [u8], UdpSocketError> {
+        let expected = self.tag_of::<T>()?;
+        let (tag, value) = split_tag(from, frame)?;
+        if tag != expected {
+            return Err(UdpSocketError::UnexpectedMessageType {
+                from,
+                expected,
+                got: tag,
+            });
+        }
+        Ok(value)
+    }
+}
+
+/// Split the tag from the start of a frame received from `from`
+fn split_tag(from: SocketAddr, frame: &[u8]) -> Result<(u32, &[u8]), UdpSocketError> {
+    if frame.len() < TAG_LEN {
+        return Err(UdpSocketError::ForeignPacket { from });
+    }
+    let (tag, value) = frame.split_at(TAG_LEN);
+    Ok((u32::from_be_bytes(tag.try_into().unwrap()), value))
+}
+
+type Decode<C> = fn(&Inbound, &C, &[u8], SocketAddr) -> Result<Box<dyn Any + Send>, UdpSocketError>;
+
+struct Decoder<C> {
+    type_name: &'static str,
+    decode: Decode<C>,
+}
+
+/// The set of message types a [`UdpSocket`](crate::UdpSocket) exchanges, each identified by a
+/// tag on the wire
+///
+/// Once a registry is set with [`set_registry`](crate::UdpSocket::set_registry), every value
+/// written is prefixed with the tag of its type, and
+/// [`read_any`](crate::UdpSocket::read_any) decodes whichever registered type arrives next.
+/// Writing or reading a type that isn't registered fails with
+/// [`UdpSocketError::UnregisteredMessageType`]. Both peers must register the same types under
+/// the same tags, which adds 4 bytes to every value.
+///
+/// # Example
+///
+/// ```no_run
+/// use serde::{Deserialize, Serialize};
+/// use sockit::{Registry, UdpSocket};
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct Ping(u32);
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct Status { healthy: bool }
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+///   let mut socket = UdpSocket::bind("127.0.0.1:0").await?;
+///   socket.set_registry(Registry::new().register::<Ping>(1).register::<Status>(2));
+///
+///   let (message, from) = socket.read_any().await?;
+///   match message.downcast::<Ping>() {
+///       Ok(Ping(n)) => println!("{from} pinged {n}"),
+///       Err(message) => println!("{from} sent a {}", message.type_name()),
+///   }
+///   Ok(())
+/// }
+/// ```
+pub struct Registry<C = BincodeCodec> {
+    tags: Arc<MessageTags>,
+    decoders: HashMap<u32, Decoder<C>>,
+}
+
+impl<C: Codec> Registry<C> {
+    /// Create a registry without any message types
+    pub fn new() -> Self {
+        Self {
+            tags: Arc::default(),
+            decoders: HashMap::new(),
+        }
+    }
+
+    /// Register the message type `T` under `tag`
+    ///
+    /// Values of `T` are fingerprinted with its full schema, including the types inside its
+    /// options, collections and enum variants.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `T` or `tag` is already registered.
+    pub fn register<T: DeserializeOwned + Send + 'static>(self, tag: u32) -> Self {
+        self.insert::<T>(Entry {
+            tag,
+            fingerprint: fingerprint::of_schema::<T>(),
+            max_size: None,
+        })
+    }
+
+    /// Register the [`Message`] type `T` under its id
+    ///
+    /// Values of `T` are fingerprinted with its declared fingerprint if it has one and its full
+    /// schema otherwise, like with [`register`](Self::register), and writing values that
+    /// exceed its declared maximum size fails with [`UdpSocketError::MessageTooLarge`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if `T` or its id is already registered.
+    pub fn register_message<T: Message>(self) -> Self {
+        self.insert::<T>(Entry {
+            tag: T::ID,
+            fingerprint: T::FINGERPRINT.unwrap_or_else(fingerprint::of_schema::<T>),
+            max_size: T::MAX_SIZE,
+        })
+    }
+
+    fn insert<T: DeserializeOwned + Send + 'static>(mut self, entry: Entry) -> Self {
+        let tag = entry.tag;
+        let type_name = std::any::type_name::<T>();
+        if let Some(existing) = self.decoders.get(&tag) {
+            panic!("tag {tag} is already registered for {}", existing.type_name);
+        }
+        let tags = Arc::make_mut(&mut self.tags);
+        if tags.by_type.insert(TypeId::of::<T>(), entry).is_some() {
+            panic!("{type_name} is already registered");
+        }
+        self.decoders.insert(
+            tag,
+            Decoder {
+                type_name,
+                decode: decode::<C, T>,
+            },
+        );
+        self
+    }
+
+    /// Get the tag of the message type `T`, if it is registered
+    pub fn tag_of<T: ?Sized>(&self) -> Option<u32> {
+        self.tags.tag_of::<T>().ok()
+    }
+
+    pub(crate) fn tags(&self) -> Arc<MessageTags> {
+        self.tags.clone()
+    }
+
+    /// Decode a value of whichever registered type is tagged in a frame received from `from`
+    pub(crate) fn decode_any(
+        &self,
+        inbound: &Inbound,
+        codec: &C,
+        frame: &[u8],
+        from: SocketAddr,
+    ) -> Result<AnyMessage, UdpSocketError> {
+        let (tag, _) = split_tag(from, inbound.checks.unwrap_envelope(frame, from)?)?;
+        let decoder = self
+            .decoders
+            .get(&tag)
+            .ok_or(UdpSocketError::UnknownMessageTag { from, tag })?;
+        Ok(AnyMessage {
+            tag,
+            type_name: decoder.type_name,
+            value: (decoder.decode)(inbound, codec, frame, from)?,
+        })
+    }
+}
+
+/// Read the next value of a registered type from `inbound`
+pub(crate) async fn read_any<C: Codec>(
+    inbound: &mut Inbound,
+    codec: &C,
+    registry: Option<&Registry<C>>,
+) -> Result<(AnyMessage, SocketAddr), UdpSocketError> {
+    let registry = registry.ok_or(UdpSocketError::NoRegistry)?;
+    let (frame, src) = inbound.recv_value_frame().await?;
+    let message = registry.decode_any(inbound, codec, &frame, src)?;
+    Ok((message, src))
+}
+
+fn decode<C: Codec, T: DeserializeOwned + Send + 'static>(
+    inbound: &Inbound,
+    codec: &C,
+    frame: &[u8],
+    from: SocketAddr,
+) -> Result<Box<dyn Any + Send>, UdpSocketError> {
+    let value: T = inbound.decode_value(codec, frame, from)?;
+    Ok(Box::new(value))
+}
+
+impl<C: Codec> Default for Registry<C> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<C> fmt::Debug for Registry<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map()
+            .entries(
+                self.decoders
+                    .iter()
+                    .map(|(tag, decoder)| (tag, decoder.type_name)),
+            )
+            .finish()
+    }
+}
+
+/// A value of one of the message types in a [`Registry`], read with
+/// [`read_any`](crate::UdpSocket::read_any)
+pub struct AnyMessage {
+    tag: u32,
+    type_name: &'static str,
+    value: Box<dyn Any + Send>,
+}
+
+impl AnyMessage {
+    /// Get the tag of the message's type
+    pub fn tag(&self) -> u32 {
+        self.tag
+    }
+
+    /// Get the name of the message's type
+    pub fn type_name(&self) -> &'static str {
+        self.type_name
+    }
+
+    /// Get whether the message is of type `T`
+    pub fn is<T: Any>(&self) -> bool {
+        self.value.is::<T>()
+    }
+
+    /// Take the message as a value of type `T`, or get it back if it is of another type
+    pub fn downcast<T: Any>(self) -> Result<T, Self> {
+        match self.value.downcast::<T>() {
+            Ok(value) => Ok(*value),
+            Err(value) => Err(Self { value, ..self }),
+        }
+    }
+
+    pub(crate) fn value_type_id(&self) -> TypeId {
+        (*self.value).type_id()
+    }
+}
+
+impl fmt::Debug for AnyMessage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AnyMessage")
+            .field("tag", &self.tag)
+            .field("type_name", &self.type_name)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Routes the values read from a [`UdpSocket`](crate::UdpSocket) to the handler registered for
+/// their type
+///
+/// The socket must have a [`Registry`] containing every type a handler is registered for.
+///
+/// # Example
+///
+/// ```no_run
+/// use serde::{Deserialize, Serialize};
+/// use sockit::{Dispatcher, Registry, UdpSocket};
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct Ping(u32);
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct Status { healthy: bool }
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+///   let mut socket = UdpSocket::bind("127.0.0.1:0").await?;
+///   socket.set_registry(Registry::new().register::<Ping>(1).register::<Status>(2));
+///
+///   let mut dispatcher = Dispatcher::new()
+///       .on(|Ping(n), from| println!("{from} pinged {n}"))
+///       .on(|status: Status, from| println!("{from} is healthy: {}", status.healthy));
+///   loop {
+///       dispatcher.dispatch(&mut socket).await?;
+///   }
+/// }
+/// ```
+#[derive(Default)]
+pub struct Dispatcher {
+    handlers: HashMap<TypeId, Handler>,
+}
+
+type Handler = Box<dyn FnMut(AnyMessage, SocketAddr) + Send>;
+
+impl Dispatcher {
+    /// Create a dispatcher without any handlers
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Call `handler` with every value of type `T`, replacing any previous handler for it
+    pub fn on<T, F>(mut self, mut handler: F) -> Self
+    where
+        T: Any + Send,
+        F: FnMut(T, SocketAddr) + Send + 'static,
+    {
+        let handler = move |message: AnyMessage, from| {
+            if let Ok(value) = message.downcast::<T>() {
+                handler(value, from);
+            }
+        };
+        self.handlers.insert(TypeId::of::<T>(), Box::new(handler));
+        self
+    }
+
+    /// Read the next value from the socket and pass it to the handler registered for its type
+    ///
+    /// Values without a handler are returned instead.
+    pub async fn dispatch<C: Codec>(
+        &mut self,
+        socket: &mut UdpSocket<C>,
+    ) -> Result<Option<(AnyMessage, SocketAddr)>, UdpSocketError> {
+        let (message, from) = socket.read_any().await?;
+        match self.handlers.get_mut(&message.value_type_id()) {
+            Some(handler) => {
+                handler(message, from);
+                Ok(None)
+            }
+            None => Ok(Some((message, from))),
+        }
+    }
+}
+
+impl fmt::Debug for Dispatcher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Dispatcher")
+            .field("handlers", &self.handlers.len())
+            .finish()
+    }
+}
@@ -0,0 +1,358 @@
+use crate::{BincodeCodec, Codec, UdpSocket, UdpSocketError};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+
+const DATA: u8 = 0;
+const ACK: u8 = 1;
+const HEADER_LEN: usize = 9;
+
+/// The number of sequence numbers remembered per peer to discard retransmitted duplicates
+const DEDUP_WINDOW: usize = 1024;
+
+/// Retransmission settings for a [`ReliableUdpSocket`]
+///
+/// Unacknowledged messages are retransmitted after the retransmission timeout (RTO), which
+/// doubles after every attempt up to `max_rto`.
+///
+/// # Example
+///
+/// ```no_run
+/// use sockit::{Reliability, ReliableUdpSocket, UdpSocket};
+/// use std::time::Duration;
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+///   let socket = UdpSocket::bind("127.0.0.1:0").await?;
+///   let mut socket = ReliableUdpSocket::new(socket);
+///   socket.set_reliability(
+///       Reliability::new()
+///           .initial_rto(Duration::from_millis(100))
+///           .max_attempts(5)
+///           .max_inbox(64),
+///   );
+///   Ok(())
+/// }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Reliability {
+    pub(crate) initial_rto: Duration,
+    pub(crate) max_rto: Duration,
+    pub(crate) max_attempts: u32,
+    pub(crate) max_peers: usize,
+    pub(crate) max_inbox: usize,
+}
+
+impl Reliability {
+    /// Create a configuration with a 200ms initial RTO, a 5s maximum RTO and 8 attempts, which
+    /// remembers duplicates from up to 1024 peers and queues up to 256 unread values
+    pub fn new() -> Self {
+        Self {
+            initial_rto: Duration::from_millis(200),
+            max_rto: Duration::from_secs(5),
+            max_attempts: 8,
+            max_peers: 1024,
+            max_inbox: 256,
+        }
+    }
+
+    /// Set how long to wait for the first acknowledgement before retransmitting
+    pub fn initial_rto(mut self, rto: Duration) -> Self {
+        self.initial_rto = rto;
+        self
+    }
+
+    /// Set the upper bound for the retransmission timeout after backing off
+    pub fn max_rto(mut self, rto: Duration) -> Self {
+        self.max_rto = rto;
+        self
+    }
+
+    /// Set how many times a message is sent before giving up
+    pub fn max_attempts(mut self, attempts: u32) -> Self {
+        self.max_attempts = attempts.max(1);
+        self
+    }
+
+    /// Set how many peers the sequence numbers of recently received values are remembered for
+    ///
+    /// When a value arrives from another peer while the cap is reached, the peer that was heard
+    /// from least recently is forgotten, so a late retransmission from it may be read twice.
+    pub fn max_peers(mut self, peers: usize) -> Self {
+        self.max_peers = peers.max(1);
+        self
+    }
+
+    /// Set how many received values are queued until they are read
+    ///
+    /// While the queue is full, new values are left unacknowledged, so that their senders
+    /// retransmit them once there is room again.
+    pub fn max_inbox(mut self, values: usize) -> Self {
+        self.max_inbox = values.max(1);
+        self
+    }
+}
+
+impl Default for Reliability {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A [`UdpSocket`] that acknowledges received values and retransmits unacknowledged ones
+///
+/// Every value is sent with a sequence number. The receiving [`ReliableUdpSocket`] replies with
+/// an acknowledgement and discards retransmitted duplicates, so [`read`](Self::read) yields each
+/// value once. Both peers must use a [`ReliableUdpSocket`].
+///
+/// Values and acknowledgements are signed, wrapped in envelopes, fingerprinted and tagged
+/// like values written by the wrapped socket. Sequencing can't be combined with reliable delivery,
+/// which numbers values itself, so [`write_reliable`](Self::write_reliable) and
+/// [`read`](Self::read) return [`UdpSocketError::SequencingUnsupported`] if it is enabled.
+///
+/// # Example
+///
+/// ```no_run
+/// use sockit::{ReliableUdpSocket, UdpSocket};
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+///   let mut socket = ReliableUdpSocket::new(UdpSocket::bind("127.0.0.1:0").await?);
+///   socket.write_reliable(&"Hello World!", "127.0.0.1:9090".parse()?).await?;
+///   let (message, from) = socket.read::<String>().await?;
+///   Ok(())
+/// }
+/// ```
+pub struct ReliableUdpSocket<C = BincodeCodec> {
+    socket: UdpSocket<C>,
+    reliability: Reliability,
+    next_sequence: u64,
+    received: HashMap<SocketAddr, Window>,
+    inbox: VecDeque<(Vec<u8>, SocketAddr)>,
+}
+
+/// The sequence numbers recently received from a single peer
+struct Window {
+    seen: HashSet<u64>,
+    order: VecDeque<u64>,
+    last_heard: Instant,
+}
+
+impl Window {
+    fn new(now: Instant) -> Self {
+        Self {
+            seen: HashSet::new(),
+            order: VecDeque::new(),
+            last_heard: now,
+        }
+    }
+
+    /// Record a sequence number, returning `false` if it was already seen
+    fn insert(&mut self, sequence: u64) -> bool {
+        if !self.seen.insert(sequence) {
+            return false;
+        }
+        self.order.push_back(sequence);
+        if self.order.len() > DEDUP_WINDOW {
+            if let Some(oldest) = self.order.pop_front() {
+                self.seen.remove(&oldest);
+            }
+        }
+        true
+    }
+}
+
+impl<C: Codec> ReliableUdpSocket<C> {
+    /// Wrap a [`UdpSocket`] to provide acknowledged delivery
+    pub fn new(socket: UdpSocket<C>) -> Self {
+        // Start at a random sequence number so a restarted peer isn't mistaken for duplicates
+        let next_sequence = crate::random_u64();
+        Self {
+            socket,
+            reliability: Reliability::new(),
+            next_sequence,
+            received: HashMap::new(),
+            inbox: VecDeque::new(),
+        }
+    }
+
+    /// Change the retransmission settings of this socket
+    pub fn set_reliability(&mut self, reliability: Reliability) {
+        self.reliability = reliability;
+    }
+
+    /// Get the retransmission settings of this socket
+    pub fn reliability(&self) -> &Reliability {
+        &self.reliability
+    }
+
+    /// Write a serializable value and wait until the peer has acknowledged it
+    ///
+    /// The value is retransmitted with exponential backoff until an acknowledgement arrives.
+    /// If none arrives after [`max_attempts`](Reliability::max_attempts) transmissions,
+    /// [`UdpSocketError::Unacknowledged`] is returned. Values received from other peers while
+    /// waiting are acknowledged and kept for the next call to [`read`](Self::read), and datagrams
+    /// from other peers that can't be read are ignored.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use sockit::{ReliableUdpSocket, UdpSocket};
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    ///   let mut socket = ReliableUdpSocket::new(UdpSocket::bind("127.0.0.1:0").await?);
+    ///   socket.write_reliable(&"Hello World!", "127.0.0.1:9090".parse()?).await?;
+    ///   Ok(())
+    /// }
+    /// ```
+    pub async fn write_reliable<T: Serialize>(
+        &mut self,
+        value: &T,
+        send_to: SocketAddr,
+    ) -> Result<(), UdpSocketError> {
+        self.socket.reject_sequencing()?;
+        let sequence = self.next_sequence;
+        self.next_sequence = self.next_sequence.wrapping_add(1);
+
+        let mut frame = header(DATA, sequence);
+        let outbound = &self.socket.outbound;
+        outbound.encode_value(&self.socket.codec, value, &mut frame)?;
+        outbound.sign(&mut frame);
+
+        let mut rto = self.reliability.initial_rto;
+        for _ in 0..self.reliability.max_attempts {
+            self.socket.send_frame(&frame, send_to).await?;
+            let wait = tokio::time::sleep(rto);
+            tokio::pin!(wait);
+            loop {
+                tokio::select! {
+                    _ = &mut wait => break,
+                    received = self.recv_message() => match received {
+                        Ok(Some(acked)) if acked == (sequence, send_to) => return Ok(()),
+                        Ok(_) => {}
+                        // A stray datagram from a third party must not abort this write
+                        Err(e) if e.sender().map_or(false, |from| from != send_to) => {}
+                        Err(e) => return Err(e),
+                    }
+                }
+            }
+            rto = (rto * 2).min(self.reliability.max_rto);
+        }
+
+        Err(UdpSocketError::Unacknowledged {
+            to: send_to,
+            attempts: self.reliability.max_attempts,
+        })
+    }
+
+    /// Read a deserializable value that was written with
+    /// [`write_reliable`](Self::write_reliable)
+    ///
+    /// The value is acknowledged before it is returned, and duplicates caused by retransmission
+    /// are discarded.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use sockit::{ReliableUdpSocket, UdpSocket};
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    ///   let mut socket = ReliableUdpSocket::new(UdpSocket::bind("127.0.0.1:0").await?);
+    ///   let (message, from) = socket.read::<String>().await?;
+    ///   Ok(())
+    /// }
+    /// ```
+    pub async fn read<T: DeserializeOwned>(&mut self) -> Result<(T, SocketAddr), UdpSocketError> {
+        self.socket.reject_sequencing()?;
+        loop {
+            if let Some((payload, src)) = self.inbox.pop_front() {
+                let value = self
+                    .socket
+                    .inbound
+                    .decode_value(&self.socket.codec, &payload, src)?;
+                return Ok((value, src));
+            }
+            self.recv_message().await?;
+        }
+    }
+
+    /// Receive a single frame, returning the sequence number and sender if it was an
+    /// acknowledgement. New values are acknowledged and queued in the inbox.
+    async fn recv_message(&mut self) -> Result<Option<(u64, SocketAddr)>, UdpSocketError> {
+        let (frame, src) = self.socket.inbound.recv_verified_frame().await?;
+        if frame.len() < HEADER_LEN {
+            return Err(UdpSocketError::MalformedFrame { from: src });
+        }
+        let mut sequence = [0; 8];
+        sequence.copy_from_slice(&frame[1..HEADER_LEN]);
+        let sequence = u64::from_be_bytes(sequence);
+
+        match frame[0] {
+            ACK => Ok(Some((sequence, src))),
+            DATA => {
+                let duplicate = self
+                    .received
+                    .get(&src)
+                    .map_or(false, |window| window.seen.contains(&sequence));
+                if !duplicate && self.inbox.len() >= self.reliability.max_inbox {
+                    // Left unacknowledged, so the peer retransmits it once values have been read
+                    return Ok(None);
+                }
+                let mut ack = header(ACK, sequence);
+                self.socket.outbound.sign(&mut ack);
+                self.socket.send_frame(&ack, src).await?;
+
+                let now = Instant::now();
+                if !self.received.contains_key(&src)
+                    && self.received.len() >= self.reliability.max_peers
+                {
+                    self.forget_least_recent_peer();
+                }
+                let window = self.received.entry(src).or_insert_with(|| Window::new(now));
+                window.last_heard = now;
+                if window.insert(sequence) {
+                    self.inbox.push_back((frame[HEADER_LEN..].to_vec(), src));
+                }
+                Ok(None)
+            }
+            _ => Err(UdpSocketError::MalformedFrame { from: src }),
+        }
+    }
+
+    fn forget_least_recent_peer(&mut self) {
+        let least_recent = self
+            .received
+            .iter()
+            .min_by_key(|(_, window)| window.last_heard)
+            .map(|(peer, _)| *peer);
+        if let Some(peer) = least_recent {
+            self.received.remove(&peer);
+        }
+    }
+
+    /// Get a reference to the underlying [`UdpSocket`]
+    pub fn get_ref(&self) -> &UdpSocket<C> {
+        &self.socket
+    }
+
+    /// Unwrap the underlying [`UdpSocket`], discarding any values that haven't been read yet
+    pub fn into_inner(self) -> UdpSocket<C> {
+        self.socket
+    }
+
+    /// Get the local address of the socket
+    pub fn local_addr(&self) -> Result<SocketAddr, UdpSocketError> {
+        self.socket.local_addr()
+    }
+}
+
+fn header(kind: u8, sequence: u64) -> Vec<u8> {
+    let mut frame = Vec::with_capacity(HEADER_LEN);
+    frame.push(kind);
+    frame.extend_from_slice(&sequence.to_be_bytes());
+    frame
+}
@@ -0,0 +1,128 @@
+use crate::UdpSocketError;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+
+/// The number of senders whose windows are kept before the least recently active is forgotten
+const MAX_SENDERS: usize = 4096;
+
+/// The counters recently received from a single sender, like the anti-replay window of IPsec
+struct Window {
+    size: u64,
+    /// When the sender was last active, counted in checked datagrams
+    last_used: u64,
+    /// The highest counter received so far
+    highest: u64,
+    /// A ring of bits indexed by counter, marking which counters near `highest` were received
+    bits: Vec<u64>,
+}
+
+impl Window {
+    fn new(size: usize, counter: u64, now: u64) -> Self {
+        let mut window = Self {
+            size: size as u64,
+            last_used: now,
+            highest: counter,
+            bits: vec![0; (size + 63) / 64],
+        };
+        window.mark(counter);
+        window
+    }
+
+    fn slot(&self, counter: u64) -> (usize, u64) {
+        let bit = counter % (self.bits.len() as u64 * 64);
+        ((bit / 64) as usize, 1 << (bit % 64))
+    }
+
+    fn mark(&mut self, counter: u64) {
+        let (word, mask) = self.slot(counter);
+        self.bits[word] |= mask;
+    }
+
+    fn unmark(&mut self, counter: u64) {
+        let (word, mask) = self.slot(counter);
+        self.bits[word] &= !mask;
+    }
+
+    fn is_marked(&self, counter: u64) -> bool {
+        let (word, mask) = self.slot(counter);
+        self.bits[word] & mask != 0
+    }
+
+    /// Record a counter, returning `false` if it was already received or is too old to tell
+    fn insert(&mut self, counter: u64) -> bool {
+        if counter > self.highest {
+            let advance = counter - self.highest;
+            if advance >= self.bits.len() as u64 * 64 {
+                self.bits.fill(0);
+            } else {
+                for cleared in self.highest + 1..counter {
+                    self.unmark(cleared);
+                }
+            }
+            self.highest = counter;
+            self.mark(counter);
+            return true;
+        }
+        if self.highest - counter >= self.size || self.is_marked(counter) {
+            return false;
+        }
+        self.mark(counter);
+        true
+    }
+}
+
+/// Per-sender replay windows for the datagram counters carried by encrypted datagrams
+#[derive(Default)]
+pub(crate) struct ReplayWindows {
+    /// Keyed by the authenticated sender id alone, so that replaying a datagram from another
+    /// address hits the same window. A restarted sender picks a new id and gets a fresh window.
+    windows: HashMap<u128, Window>,
+    /// The number of datagrams checked so far, used as a clock for evicting idle senders
+    checked: u64,
+}
+
+impl ReplayWindows {
+    /// Check that the datagram numbered `counter` by `sender` wasn't received before
+    ///
+    /// `from` is only used to report a replay.
+    pub(crate) fn check(
+        &mut self,
+        size: usize,
+        from: SocketAddr,
+        sender: u128,
+        counter: u64,
+    ) -> Result<(), UdpSocketError> {
+        self.checked += 1;
+        let inserted = match self.windows.get_mut(&sender) {
+            Some(window) => {
+                window.last_used = self.checked;
+                window.insert(counter)
+            }
+            None => {
+                if self.windows.len() >= MAX_SENDERS {
+                    self.evict_idlest();
+                }
+                self.windows
+                    .insert(sender, Window::new(size, counter, self.checked));
+                true
+            }
+        };
+        if inserted {
+            Ok(())
+        } else {
+            Err(UdpSocketError::ReplayDetected { from })
+        }
+    }
+
+    /// Forget the window of the sender that has been inactive for longest
+    fn evict_idlest(&mut self) {
+        let idlest = self
+            .windows
+            .iter()
+            .min_by_key(|(_, window)| window.last_used)
+            .map(|(sender, _)| *sender);
+        if let Some(sender) = idlest {
+            self.windows.remove(&sender);
+        }
+    }
+}
@@ -0,0 +1,307 @@
+use crate::pipeline::{Outbound, ValueChecks};
+use crate::{BincodeCodec, Codec, Reliability, UdpSocket, UdpSocketError};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::future::Future;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::{mpsc, oneshot};
+use tokio::time::Instant;
+
+const REQUEST: u8 = 0;
+const RESPONSE: u8 = 1;
+const HEADER_LEN: usize = 9;
+
+/// The calls waiting for a response, with the addresses their requests were sent to
+type Pending = Arc<Mutex<HashMap<u64, (SocketAddr, oneshot::Sender<Vec<u8>>)>>>;
+
+/// A client that sends requests over a [`UdpSocket`] and waits for the matching responses
+///
+/// Every request is tagged with a correlation id that the server echoes in its response, so
+/// many calls can be outstanding at once. The socket is driven by a background task, which
+/// stops when the client is dropped.
+///
+/// Requests are retransmitted according to the client's [`Reliability`] settings until a
+/// response arrives, so servers may see the same request more than once.
+///
+/// Requests and responses are signed, wrapped in envelopes, fingerprinted and tagged like values
+/// written by the sockets. Sequencing can't be combined with RPC, which correlates requests and
+/// responses itself, so [`call`](Self::call) and [`serve`](UdpSocket::serve) return
+/// [`UdpSocketError::SequencingUnsupported`] if it is enabled.
+///
+/// # Example
+///
+/// ```no_run
+/// use sockit::{RpcClient, UdpSocket};
+/// use std::time::Duration;
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+///   let client = RpcClient::new(UdpSocket::bind("127.0.0.1:0").await?);
+///   let server = "127.0.0.1:9090".parse()?;
+///   let sum: u32 = client.call(server, &(1u32, 2u32), Duration::from_secs(1)).await?;
+///   Ok(())
+/// }
+/// ```
+pub struct RpcClient<C = BincodeCodec> {
+    codec: C,
+    outbound: Outbound,
+    checks: ValueChecks,
+    sequencing: bool,
+    local_addr: SocketAddr,
+    reliability: Reliability,
+    next_id: AtomicU64,
+    pending: Pending,
+    outgoing: mpsc::UnboundedSender<(Vec<u8>, SocketAddr)>,
+}
+
+impl<C: Codec + Clone + Send + Sync + 'static> RpcClient<C> {
+    /// Create a client that takes ownership of the socket and drives it in a background task
+    ///
+    /// # Panics
+    ///
+    /// Panics if called outside of a Tokio runtime.
+    pub fn new(socket: UdpSocket<C>) -> Self {
+        let codec = socket.codec.clone();
+        let outbound = socket.outbound.clone();
+        let checks = socket.inbound.checks.clone();
+        let sequencing = socket.reject_sequencing().is_err();
+        let local_addr = socket
+            .local_addr()
+            .expect("a bound socket has a local address");
+        let pending = Pending::default();
+        let (outgoing, requests) = mpsc::unbounded_channel();
+        tokio::spawn(drive(socket, requests, pending.clone()));
+
+        Self {
+            codec,
+            outbound,
+            checks,
+            sequencing,
+            local_addr,
+            reliability: Reliability::new(),
+            next_id: AtomicU64::new(crate::random_u64()),
+            pending,
+            outgoing,
+        }
+    }
+
+    /// Change the retransmission settings used for requests
+    pub fn set_reliability(&mut self, reliability: Reliability) {
+        self.reliability = reliability;
+    }
+
+    /// Send a request to `addr` and wait for its response
+    ///
+    /// The request is retransmitted with exponential backoff until a response arrives. If no
+    /// response arrives within `timeout`, or after the maximum number of attempts,
+    /// [`UdpSocketError::RpcTimeout`] is returned.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use sockit::{RpcClient, UdpSocket};
+    /// use std::time::Duration;
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    ///   let client = RpcClient::new(UdpSocket::bind("127.0.0.1:0").await?);
+    ///   let reply: String = client
+    ///       .call("127.0.0.1:9090".parse()?, &"ping", Duration::from_secs(1))
+    ///       .await?;
+    ///   Ok(())
+    /// }
+    /// ```
+    pub async fn call<Req: Serialize, Resp: DeserializeOwned>(
+        &self,
+        addr: SocketAddr,
+        request: &Req,
+        timeout: Duration,
+    ) -> Result<Resp, UdpSocketError> {
+        if self.sequencing {
+            return Err(UdpSocketError::SequencingUnsupported);
+        }
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let mut frame = header(REQUEST, id);
+        self.outbound
+            .encode_value(&self.codec, request, &mut frame)?;
+        self.outbound.sign(&mut frame);
+
+        let (sender, mut receiver) = oneshot::channel();
+        let _guard = PendingGuard::insert(&self.pending, id, addr, sender);
+
+        let deadline = Instant::now() + timeout;
+        let mut rto = self.reliability.initial_rto;
+        for _ in 0..self.reliability.max_attempts {
+            if self.outgoing.send((frame.clone(), addr)).is_err() {
+                return Err(UdpSocketError::RpcClosed);
+            }
+            let wait = (Instant::now() + rto).min(deadline);
+            match tokio::time::timeout_at(wait, &mut receiver).await {
+                Ok(Ok(response)) => return self.checks.decode_value(&self.codec, &response, addr),
+                Ok(Err(_)) => return Err(UdpSocketError::RpcClosed),
+                Err(_) if Instant::now() >= deadline => break,
+                Err(_) => rto = (rto * 2).min(self.reliability.max_rto),
+            }
+        }
+
+        Err(UdpSocketError::RpcTimeout { to: addr })
+    }
+
+    /// Get the local address of the client's socket
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+}
+
+/// Removes a call from the pending map when it completes, times out or is cancelled
+struct PendingGuard<'a> {
+    pending: &'a Pending,
+    id: u64,
+}
+
+impl<'a> PendingGuard<'a> {
+    fn insert(
+        pending: &'a Pending,
+        id: u64,
+        addr: SocketAddr,
+        sender: oneshot::Sender<Vec<u8>>,
+    ) -> Self {
+        pending.lock().unwrap().insert(id, (addr, sender));
+        Self { pending, id }
+    }
+}
+
+impl Drop for PendingGuard<'_> {
+    fn drop(&mut self) {
+        self.pending.lock().unwrap().remove(&self.id);
+    }
+}
+
+/// Send queued requests and route responses to the calls waiting for them
+///
+/// A response only completes a call if it comes from the address the request was sent to.
+/// Frames that can't be received are skipped, but if the socket fails, every pending call is
+/// failed and the task stops.
+async fn drive<C: Codec>(
+    mut socket: UdpSocket<C>,
+    mut requests: mpsc::UnboundedReceiver<(Vec<u8>, SocketAddr)>,
+    pending: Pending,
+) {
+    loop {
+        tokio::select! {
+            request = requests.recv() => match request {
+                Some((frame, addr)) => {
+                    // A failed send is retried by the caller's retransmission
+                    let _ = socket.send_frame(&frame, addr).await;
+                }
+                None => return,
+            },
+            received = socket.inbound.recv_verified_frame() => {
+                let (frame, src) = match received {
+                    Ok(received) => received,
+                    Err(UdpSocketError::IoError(_)) => {
+                        // Dropping the senders fails the calls with `RpcClosed`
+                        pending.lock().unwrap().clear();
+                        return;
+                    }
+                    Err(_) => continue,
+                };
+                let (id, payload) = match parse(&frame) {
+                    Some((RESPONSE, id, payload)) => (id, payload),
+                    _ => continue,
+                };
+                let mut pending = pending.lock().unwrap();
+                if pending.get(&id).map_or(false, |(addr, _)| *addr == src) {
+                    let (_, sender) = pending.remove(&id).unwrap();
+                    let _ = sender.send(payload.to_vec());
+                }
+            }
+        }
+    }
+}
+
+impl<C: Codec> UdpSocket<C> {
+    /// Answer requests sent by an [`RpcClient`] until an I/O error occurs
+    ///
+    /// Each request is passed to `handler` together with the sender's address, and the value it
+    /// returns is written back to the sender. Requests that can't be decoded and responses
+    /// that can't be encoded or sent are skipped.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use sockit::UdpSocket;
+    /// use std::net::SocketAddr;
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    ///   let mut socket = UdpSocket::bind("127.0.0.1:9090").await?;
+    ///   socket
+    ///       .serve(|(a, b): (u32, u32), _from: SocketAddr| async move { a + b })
+    ///       .await?;
+    ///   Ok(())
+    /// }
+    /// ```
+    pub async fn serve<Req, Resp, F, Fut>(&mut self, mut handler: F) -> Result<(), UdpSocketError>
+    where
+        Req: DeserializeOwned,
+        Resp: Serialize,
+        F: FnMut(Req, SocketAddr) -> Fut,
+        Fut: Future<Output = Resp>,
+    {
+        self.reject_sequencing()?;
+        loop {
+            let (frame, from) = match self.inbound.recv_verified_frame().await {
+                Ok(received) => received,
+                Err(e @ UdpSocketError::IoError(_)) => return Err(e),
+                Err(_) => continue,
+            };
+            let (id, payload) = match parse(&frame) {
+                Some((REQUEST, id, payload)) => (id, payload),
+                _ => continue,
+            };
+            let request = match self
+                .inbound
+                .decode_value::<_, Req>(&self.codec, payload, from)
+            {
+                Ok(request) => request,
+                Err(_) => continue,
+            };
+
+            let response = handler(request, from).await;
+            let mut frame = header(RESPONSE, id);
+            if self
+                .outbound
+                .encode_value(&self.codec, &response, &mut frame)
+                .is_err()
+            {
+                continue;
+            }
+            self.outbound.sign(&mut frame);
+            match self.send_frame(&frame, from).await {
+                Err(e @ UdpSocketError::IoError(_)) => return Err(e),
+                _ => continue,
+            }
+        }
+    }
+}
+
+fn header(kind: u8, id: u64) -> Vec<u8> {
+    let mut frame = Vec::with_capacity(HEADER_LEN);
+    frame.push(kind);
+    frame.extend_from_slice(&id.to_be_bytes());
+    frame
+}
+
+fn parse(frame: &[u8]) -> Option<(u8, u64, &[u8])> {
+    if frame.len() < HEADER_LEN {
+        return None;
+    }
+    let mut id = [0; 8];
+    id.copy_from_slice(&frame[1..HEADER_LEN]);
+    Some((frame[0], u64::from_be_bytes(id), &frame[HEADER_LEN..]))
+}
@@ -0,0 +1,210 @@
+use crate::pipeline::SignedFrame;
+use crate::UdpSocketError;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::net::SocketAddr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// The number of bytes prepended to every value when sequencing is enabled
+pub(crate) const HEADER_LEN: usize = 16;
+
+/// How many peers sequence numbers are kept for in each direction
+const MAX_PEERS: usize = 1024;
+
+/// How long a peer must stay silent before a value it would discard resets its state instead
+const RESYNC_AFTER: Duration = Duration::from_secs(5);
+
+/// How a [`UdpSocket`](crate::UdpSocket) orders values received from each peer
+///
+/// When sequencing is enabled, every value is written with a per-destination sequence number
+/// and duplicates are always discarded by [`read`](crate::UdpSocket::read). Both peers must
+/// enable sequencing.
+///
+/// Every destination is sent a new session, stamped with the time it was first written to, so
+/// that values from a restarted peer start over while late values from its previous session are
+/// discarded. Sequence numbers are remembered for up to 1024 peers in each direction, forgetting
+/// the least recently used one, and a destination that was forgotten is sent a new session.
+///
+/// If nothing was delivered from a peer for 5 seconds, a value that would be discarded resets
+/// its state instead. This keeps a spoofed or corrupted header from locking out the real peer,
+/// at the cost of delivering a very late duplicate after such a pause.
+///
+/// # Example
+///
+/// ```no_run
+/// use sockit::{Sequencing, UdpSocket};
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+///   let mut socket = UdpSocket::bind("127.0.0.1:0").await?;
+///   socket.set_sequencing(Sequencing::Ordered { max_buffered: 64 });
+///   Ok(())
+/// }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Sequencing {
+    /// Deliver values strictly in the order they were written
+    ///
+    /// Values that arrive early are buffered until the gap before them is filled. If more than
+    /// `max_buffered` values are waiting, the gap is assumed to be lost and skipped.
+    Ordered { max_buffered: usize },
+    /// Deliver only values that are newer than the last one delivered, dropping stale ones
+    Latest,
+}
+
+/// The receive state for a single peer
+struct Incoming {
+    session: u64,
+    /// The next sequence number to deliver in ordered mode, or one past the last delivered
+    next: u64,
+    /// Values that arrived early, with the public keys that signed them
+    buffered: BTreeMap<u64, (Vec<u8>, Option<[u8; 32]>)>,
+    last_delivered: Instant,
+}
+
+impl Incoming {
+    fn new(session: u64, next: u64, now: Instant) -> Self {
+        Self {
+            session,
+            next,
+            buffered: BTreeMap::new(),
+            last_delivered: now,
+        }
+    }
+}
+
+/// The send state for a single destination
+struct Outgoing {
+    session: u64,
+    next: u64,
+    last_used: Instant,
+}
+
+/// Stamps outgoing values with per-destination sequence numbers
+#[derive(Default)]
+pub(crate) struct SequenceCounters {
+    outgoing: Mutex<HashMap<SocketAddr, Outgoing>>,
+}
+
+impl SequenceCounters {
+    /// Append the sequencing header for the next value sent to `to`
+    pub(crate) fn stamp(&self, to: SocketAddr, buf: &mut Vec<u8>) {
+        let mut outgoing = self.outgoing.lock().unwrap();
+        let now = Instant::now();
+        if !outgoing.contains_key(&to) && outgoing.len() >= MAX_PEERS {
+            let least_recent = outgoing
+                .iter()
+                .min_by_key(|(_, counter)| counter.last_used)
+                .map(|(peer, _)| *peer);
+            if let Some(peer) = least_recent {
+                outgoing.remove(&peer);
+            }
+        }
+        let counter = outgoing.entry(to).or_insert_with(|| Outgoing {
+            session: crate::unique_timestamp(),
+            next: 0,
+            last_used: now,
+        });
+        if counter.next == u64::MAX {
+            // Receivers reject the last sequence number, so start over in a new session
+            counter.session = crate::unique_timestamp();
+            counter.next = 0;
+        }
+        buf.extend_from_slice(&counter.session.to_be_bytes());
+        buf.extend_from_slice(&counter.next.to_be_bytes());
+        counter.next += 1;
+        counter.last_used = now;
+    }
+}
+
+/// Discards duplicate incoming values and orders them per peer
+#[derive(Default)]
+pub(crate) struct Sequencer {
+    incoming: HashMap<SocketAddr, Incoming>,
+    ready: VecDeque<SignedFrame>,
+}
+
+impl Sequencer {
+    /// Take the next value that is ready to be delivered
+    pub(crate) fn pop_ready(&mut self) -> Option<SignedFrame> {
+        self.ready.pop_front()
+    }
+
+    /// Accept a sequenced frame received from `from` and signed by `signer`, queueing any values
+    /// that became ready
+    pub(crate) fn accept(
+        &mut self,
+        mode: Sequencing,
+        from: SocketAddr,
+        frame: &[u8],
+        signer: Option<[u8; 32]>,
+    ) -> Result<(), UdpSocketError> {
+        if frame.len() < HEADER_LEN {
+            return Err(UdpSocketError::MalformedFrame { from });
+        }
+        let (header, payload) = frame.split_at(HEADER_LEN);
+        let (session, sequence) = header.split_at(8);
+        let session = u64::from_be_bytes(session.try_into().unwrap());
+        let sequence = u64::from_be_bytes(sequence.try_into().unwrap());
+        let after = sequence
+            .checked_add(1)
+            .ok_or(UdpSocketError::MalformedFrame { from })?;
+
+        let now = Instant::now();
+        if !self.incoming.contains_key(&from) && self.incoming.len() >= MAX_PEERS {
+            self.forget_least_recent_peer();
+        }
+        let incoming = self
+            .incoming
+            .entry(from)
+            .or_insert_with(|| Incoming::new(session, 0, now));
+        if session > incoming.session {
+            // The peer restarted, so its sequence numbers start over
+            *incoming = Incoming::new(session, 0, now);
+        } else if session < incoming.session || sequence < incoming.next {
+            if now.duration_since(incoming.last_delivered) < RESYNC_AFTER {
+                // A duplicate, or a late value from before the peer restarted
+                return Ok(());
+            }
+            *incoming = Incoming::new(session, sequence, now);
+        }
+
+        match mode {
+            Sequencing::Latest => {
+                incoming.next = after;
+                incoming.last_delivered = now;
+                self.ready.push_back((payload.to_vec(), from, signer));
+            }
+            Sequencing::Ordered { max_buffered } => {
+                incoming
+                    .buffered
+                    .entry(sequence)
+                    .or_insert((payload.to_vec(), signer));
+                if incoming.buffered.len() > max_buffered {
+                    // Give up on the missing values and resume from the oldest buffered one
+                    if let Some(&oldest) = incoming.buffered.keys().next() {
+                        incoming.next = oldest;
+                    }
+                }
+                while let Some((payload, signer)) = incoming.buffered.remove(&incoming.next) {
+                    // Buffered sequence numbers are below `u64::MAX`, so this can't overflow
+                    incoming.next += 1;
+                    incoming.last_delivered = now;
+                    self.ready.push_back((payload, from, signer));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn forget_least_recent_peer(&mut self) {
+        let least_recent = self
+            .incoming
+            .iter()
+            .min_by_key(|(_, incoming)| incoming.last_delivered)
+            .map(|(peer, _)| *peer);
+        if let Some(peer) = least_recent {
+            self.incoming.remove(&peer);
+        }
+    }
+}
@@ -0,0 +1,218 @@
+use crate::registry::AnyMessage;
+use crate::split::SendHalf;
+use crate::{BincodeCodec, Codec, UdpSocket, UdpSocketError};
+use serde::Serialize;
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::fmt;
+use std::future::Future;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::Arc;
+use tokio::sync::Semaphore;
+use tokio_util::sync::CancellationToken;
+
+type Handler<C> = Arc<
+    dyn Fn(AnyMessage, SocketAddr, SendHalf<C>) -> Pin<Box<dyn Future<Output = ()> + Send>>
+        + Send
+        + Sync,
+>;
+
+/// Serves the message types in a socket's [`Registry`](crate::Registry) with async handlers
+///
+/// Every value read is passed to the handler registered for its type, and the values returned
+/// by [`handle`](Self::handle) handlers are written back to the sender. Handlers run
+/// concurrently on the Tokio runtime, up to the [concurrency limit](Self::concurrency_limit);
+/// once it is reached, no more values are read until a handler finishes. Values of types
+/// without a handler are ignored.
+///
+/// # Example
+///
+/// ```no_run
+/// use serde::{Deserialize, Serialize};
+/// use sockit::{CancellationToken, Registry, Server, UdpSocket};
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct Ping(u32);
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct Pong(u32);
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct Log(String);
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+///   let mut socket = UdpSocket::bind("127.0.0.1:9090").await?;
+///   socket.set_registry(
+///       Registry::new()
+///           .register::<Ping>(1)
+///           .register::<Pong>(2)
+///           .register::<Log>(3),
+///   );
+///
+///   let shutdown = CancellationToken::new();
+///   tokio::spawn({
+///       let shutdown = shutdown.clone();
+///       async move {
+///           let _ = tokio::signal::ctrl_c().await;
+///           shutdown.cancel();
+///       }
+///   });
+///
+///   Server::new(socket)
+///       .handle(|Ping(n), _from| async move { Pong(n) })
+///       .on(|Log(line), from| async move { println!("{from}: {line}") })
+///       .run(shutdown)
+///       .await?;
+///   Ok(())
+/// }
+/// ```
+pub struct Server<C = BincodeCodec> {
+    socket: UdpSocket<C>,
+    handlers: HashMap<TypeId, Handler<C>>,
+    concurrency_limit: usize,
+}
+
+impl<C> Server<C>
+where
+    C: Codec + Clone + Send + Sync + 'static,
+{
+    /// The number of handlers that run at once unless configured otherwise
+    pub const DEFAULT_CONCURRENCY_LIMIT: usize = 64;
+
+    /// Create a server without any handlers for a socket with a [`Registry`](crate::Registry)
+    pub fn new(socket: UdpSocket<C>) -> Self {
+        Self {
+            socket,
+            handlers: HashMap::new(),
+            concurrency_limit: Self::DEFAULT_CONCURRENCY_LIMIT,
+        }
+    }
+
+    /// Set the largest number of handlers that run at once
+    ///
+    /// # Panics
+    ///
+    /// Panics if `limit` is zero or larger than [`u32::MAX`].
+    pub fn concurrency_limit(mut self, limit: usize) -> Self {
+        assert!(
+            limit > 0 && limit <= u32::MAX as usize,
+            "the concurrency limit must be between 1 and {}, got {limit}",
+            u32::MAX
+        );
+        self.concurrency_limit = limit;
+        self
+    }
+
+    /// Answer every value of type `T` with the value `handler` returns for it
+    ///
+    /// The reply is written to the address the value came from, so its type must be registered
+    /// too. Replies that can't be written are dropped. This replaces any previous handler for
+    /// `T`.
+    pub fn handle<T, R, F, Fut>(self, handler: F) -> Self
+    where
+        T: Any + Send,
+        R: Serialize + Send + Sync + 'static,
+        F: Fn(T, SocketAddr) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = R> + Send + 'static,
+    {
+        self.insert::<T, _>(move |value, from, send| {
+            let reply = handler(value, from);
+            async move {
+                let reply = reply.await;
+                // Like a lost datagram, a reply that can't be sent is left to the peer to retry
+                let _ = send.write(&reply, from).await;
+            }
+        })
+    }
+
+    /// Call `handler` with every value of type `T` without replying
+    ///
+    /// This replaces any previous handler for `T`.
+    pub fn on<T, F, Fut>(self, handler: F) -> Self
+    where
+        T: Any + Send,
+        F: Fn(T, SocketAddr) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.insert::<T, _>(move |value, from, _| handler(value, from))
+    }
+
+    fn insert<T, Fut>(
+        mut self,
+        handler: impl Fn(T, SocketAddr, SendHalf<C>) -> Fut + Send + Sync + 'static,
+    ) -> Self
+    where
+        T: Any + Send,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let handler: Handler<C> =
+            Arc::new(move |message, from, send| match message.downcast::<T>() {
+                Ok(value) => Box::pin(handler(value, from, send)),
+                Err(_) => Box::pin(async {}),
+            });
+        self.handlers.insert(TypeId::of::<T>(), handler);
+        self
+    }
+
+    /// Get the local address of the server's socket
+    pub fn local_addr(&self) -> Result<SocketAddr, UdpSocketError> {
+        self.socket.local_addr()
+    }
+
+    /// Serve values until `shutdown` is cancelled or an I/O error occurs
+    ///
+    /// Once `shutdown` is cancelled, no more values are read, and this method returns after
+    /// the handlers that are still running have finished. Values that can't be read, for example
+    /// because they fail to decode, are skipped. Without a registry on the socket, this fails
+    /// with [`UdpSocketError::NoRegistry`] right away.
+    pub async fn run(self, shutdown: CancellationToken) -> Result<(), UdpSocketError> {
+        let (send, mut recv) = self.socket.into_split();
+        let permits = Arc::new(Semaphore::new(self.concurrency_limit));
+
+        let result = loop {
+            let permit = tokio::select! {
+                _ = shutdown.cancelled() => break Ok(()),
+                permit = permits.clone().acquire_owned() => {
+                    permit.expect("the semaphore is never closed")
+                }
+            };
+            let (message, from) = tokio::select! {
+                _ = shutdown.cancelled() => break Ok(()),
+                received = recv.read_any() => match received {
+                    Ok(received) => received,
+                    Err(e @ (UdpSocketError::IoError(_) | UdpSocketError::NoRegistry)) => {
+                        break Err(e)
+                    }
+                    Err(_) => continue,
+                },
+            };
+            let handler = match self.handlers.get(&message.value_type_id()) {
+                Some(handler) => handler,
+                None => continue,
+            };
+            let handling = handler(message, from, send.clone());
+            tokio::spawn(async move {
+                handling.await;
+                drop(permit);
+            });
+        };
+
+        // Every running handler holds a permit, so getting all of them waits for the handlers
+        let _ = permits
+            .acquire_many(self.concurrency_limit as u32)
+            .await
+            .expect("the semaphore is never closed");
+        result
+    }
+}
+
+impl<C> fmt::Debug for Server<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Server")
+            .field("handlers", &self.handlers.len())
+            .field("concurrency_limit", &self.concurrency_limit)
+            .finish_non_exhaustive()
+    }
+}
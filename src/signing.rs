@@ -0,0 +1,141 @@
+use crate::encryption::RejectPolicy;
+use crate::UdpSocketError;
+use ed25519_dalek::{Signature, Signer, SigningKey, VerifyingKey};
+use std::collections::HashSet;
+use std::fmt;
+use std::net::SocketAddr;
+
+const PUBLIC_KEY_LEN: usize = 32;
+const SIGNATURE_LEN: usize = 64;
+
+/// The number of bytes signing appends to every value
+pub(crate) const TRAILER_LEN: usize = PUBLIC_KEY_LEN + SIGNATURE_LEN;
+
+/// Ed25519 signatures on the values written and read by a [`UdpSocket`](crate::UdpSocket)
+///
+/// If a private key is set, every value written is signed with it. If any public keys are
+/// trusted, every value read must carry a valid signature by one of them, and
+/// [`read_signed`](crate::UdpSocket::read_signed) tells which one signed it. Values that are
+/// unsigned, tampered with or signed by an untrusted key are handled according to the
+/// [`RejectPolicy`].
+///
+/// Signatures cover the serialized value along with its sequencing header, so unlike
+/// [`Encryption`](crate::Encryption) they prove to anyone holding the public key which node
+/// produced it. They are verified before a value is sequenced, so forged values can't make the
+/// real ones look stale.
+///
+/// # Example
+///
+/// ```no_run
+/// use sockit::{Signing, UdpSocket};
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+///   let private_key = [7; 32]; // Generated randomly and kept secret
+///   let sensor_key = [0; 32]; // The public key of a peer, distributed out of band
+///
+///   let mut socket = UdpSocket::bind("127.0.0.1:0").await?;
+///   socket.set_signing(Signing::new().sign_with(private_key).trust(sensor_key));
+///   let (reading, from, signer) = socket.read_signed::<f64>().await?;
+///   Ok(())
+/// }
+/// ```
+#[derive(Clone, Default)]
+pub struct Signing {
+    key: Option<SigningKey>,
+    trusted: HashSet<[u8; 32]>,
+    on_failure: RejectPolicy,
+}
+
+impl Signing {
+    /// Create a configuration that neither signs nor verifies values
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sign every value written with this Ed25519 private key
+    pub fn sign_with(mut self, private_key: [u8; 32]) -> Self {
+        self.key = Some(SigningKey::from_bytes(&private_key));
+        self
+    }
+
+    /// Accept values signed by the owner of this Ed25519 public key
+    pub fn trust(mut self, public_key: [u8; 32]) -> Self {
+        self.trusted.insert(public_key);
+        self
+    }
+
+    /// Set what happens to values that aren't signed by a trusted key
+    ///
+    /// By default, `read` returns [`UdpSocketError::InvalidSignature`] or
+    /// [`UdpSocketError::UntrustedSigner`].
+    pub fn on_failure(mut self, policy: RejectPolicy) -> Self {
+        self.on_failure = policy;
+        self
+    }
+
+    /// Get the public key matching the private key values are signed with, if any
+    pub fn public_key(&self) -> Option<[u8; 32]> {
+        self.key.as_ref().map(|key| key.verifying_key().to_bytes())
+    }
+
+    /// Get whether received values are verified, which is the case once a key is trusted
+    pub fn verifies(&self) -> bool {
+        !self.trusted.is_empty()
+    }
+
+    pub(crate) fn failure_policy(&self) -> RejectPolicy {
+        self.on_failure
+    }
+
+    /// Append the public key and a signature of the frame to it
+    pub(crate) fn sign(&self, frame: &mut Vec<u8>) {
+        let key = match &self.key {
+            Some(key) => key,
+            None => return,
+        };
+        let signature = key.sign(frame);
+        frame.extend_from_slice(key.verifying_key().as_bytes());
+        frame.extend_from_slice(&signature.to_bytes());
+    }
+
+    /// Check the signature at the end of a frame received from `from`, removing it and
+    /// returning the signer's public key
+    pub(crate) fn verify(
+        &self,
+        from: SocketAddr,
+        frame: &mut Vec<u8>,
+    ) -> Result<[u8; 32], UdpSocketError> {
+        let invalid = UdpSocketError::InvalidSignature { from };
+        let start = match frame.len().checked_sub(TRAILER_LEN) {
+            Some(start) => start,
+            None => return Err(invalid),
+        };
+        let (value, trailer) = frame.split_at(start);
+        let (public_key, signature) = trailer.split_at(PUBLIC_KEY_LEN);
+        let public_key: [u8; 32] = public_key.try_into().unwrap();
+        if !self.trusted.contains(&public_key) {
+            return Err(UdpSocketError::UntrustedSigner { from, public_key });
+        }
+
+        let verifying_key = VerifyingKey::from_bytes(&public_key).map_err(|_| invalid)?;
+        let signature = Signature::from_slice(signature)
+            .map_err(|_| UdpSocketError::InvalidSignature { from })?;
+        verifying_key
+            .verify_strict(value, &signature)
+            .map_err(|_| UdpSocketError::InvalidSignature { from })?;
+        frame.truncate(start);
+        Ok(public_key)
+    }
+}
+
+impl fmt::Debug for Signing {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Never print the private key
+        f.debug_struct("Signing")
+            .field("public_key", &self.public_key())
+            .field("trusted", &self.trusted)
+            .field("on_failure", &self.on_failure)
+            .finish()
+    }
+}
@@ -0,0 +1,116 @@
+use crate::pipeline::{Inbound, Outbound};
+use crate::registry::{self, AnyMessage, Registry};
+use crate::{BincodeCodec, Codec, UdpSocket, UdpSocketError};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+/// The sending half of a [`UdpSocket`], created by [`UdpSocket::into_split`]
+///
+/// A [`SendHalf`] can be cloned to write from many tasks at once. All clones share the
+/// underlying socket and its configuration.
+#[derive(Clone)]
+pub struct SendHalf<C = BincodeCodec> {
+    codec: C,
+    outbound: Outbound,
+}
+
+/// The receiving half of a [`UdpSocket`], created by [`UdpSocket::into_split`]
+///
+/// A [`RecvHalf`] owns the socket's receive buffer, so it can read while the [`SendHalf`]
+/// is writing from other tasks.
+pub struct RecvHalf<C = BincodeCodec> {
+    codec: C,
+    inbound: Inbound,
+    registry: Option<Arc<Registry<C>>>,
+}
+
+impl<C: Codec + Clone> UdpSocket<C> {
+    /// Split the socket into a [`SendHalf`] and a [`RecvHalf`] that can be used concurrently
+    ///
+    /// Both halves share the underlying [`tokio::net::UdpSocket`] and keep the configuration
+    /// the socket had when it was split.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use sockit::UdpSocket;
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    ///   let (send, mut recv) = UdpSocket::bind("127.0.0.1:0").await?.into_split();
+    ///
+    ///   tokio::spawn(async move {
+    ///       while let Ok((message, from)) = recv.read::<String>().await {
+    ///           println!("{from}: {message}");
+    ///       }
+    ///   });
+    ///
+    ///   send.write(&"Hello World!", "127.0.0.1:9090".parse()?).await?;
+    ///   Ok(())
+    /// }
+    /// ```
+    pub fn into_split(self) -> (SendHalf<C>, RecvHalf<C>) {
+        let send = SendHalf {
+            codec: self.codec.clone(),
+            outbound: self.outbound,
+        };
+        let recv = RecvHalf {
+            codec: self.codec,
+            inbound: self.inbound,
+            registry: self.registry,
+        };
+        (send, recv)
+    }
+}
+
+impl<C: Codec> SendHalf<C> {
+    /// Write a serializable value to the socket
+    ///
+    /// See [`UdpSocket::write`].
+    pub async fn write<T: Serialize>(
+        &self,
+        value: &T,
+        send_to: SocketAddr,
+    ) -> Result<(), UdpSocketError> {
+        let frame = self.outbound.value_frame(&self.codec, value, send_to)?;
+        self.outbound.send_frame(&frame, send_to).await
+    }
+
+    /// Get the local address of the socket
+    pub fn local_addr(&self) -> Result<SocketAddr, UdpSocketError> {
+        Ok(self.outbound.socket.local_addr()?)
+    }
+
+    pub(crate) fn into_parts(self) -> (C, Outbound) {
+        (self.codec, self.outbound)
+    }
+}
+
+impl<C: Codec> RecvHalf<C> {
+    /// Read a deserializable value from the socket
+    ///
+    /// See [`UdpSocket::read`].
+    pub async fn read<T: DeserializeOwned>(&mut self) -> Result<(T, SocketAddr), UdpSocketError> {
+        let (frame, src) = self.inbound.recv_value_frame().await?;
+        let value = self.inbound.decode_value(&self.codec, &frame, src)?;
+        Ok((value, src))
+    }
+
+    /// Read a value of whichever type in the socket's [`Registry`] arrives next
+    ///
+    /// See [`UdpSocket::read_any`].
+    pub async fn read_any(&mut self) -> Result<(AnyMessage, SocketAddr), UdpSocketError> {
+        registry::read_any(&mut self.inbound, &self.codec, self.registry.as_deref()).await
+    }
+
+    /// Get the local address of the socket
+    pub fn local_addr(&self) -> Result<SocketAddr, UdpSocketError> {
+        Ok(self.inbound.socket.local_addr()?)
+    }
+
+    pub(crate) fn into_parts(self) -> (C, Inbound) {
+        (self.codec, self.inbound)
+    }
+}
@@ -0,0 +1,180 @@
+use crate::pipeline::{Inbound, Outbound};
+use crate::{BincodeCodec, Codec, RecvHalf, SendHalf, UdpSocket, UdpSocketError};
+use futures_core::Stream;
+use futures_sink::Sink;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::future::Future;
+use std::marker::PhantomData;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+
+type RecvFuture = Pin<
+    Box<dyn Future<Output = (Box<Inbound>, Result<(Vec<u8>, SocketAddr), UdpSocketError>)> + Send>,
+>;
+type SendFuture = Pin<Box<dyn Future<Output = Result<(), UdpSocketError>> + Send>>;
+
+/// A [`Stream`] of values read from a socket, created by [`UdpSocket::into_stream`] or
+/// [`RecvHalf::into_stream`]
+///
+/// Each item is the result of a single [`read`](UdpSocket::read). Datagrams are only received
+/// while the stream is polled, and dropping the stream never loses a value that was already
+/// yielded.
+pub struct UdpStream<T, C = BincodeCodec> {
+    codec: C,
+    state: RecvState,
+    _value: PhantomData<fn() -> T>,
+}
+
+enum RecvState {
+    Idle(Box<Inbound>),
+    Receiving(RecvFuture),
+    Empty,
+}
+
+/// A [`Sink`] of values written to a socket, created by [`UdpSocket::into_sink`] or
+/// [`SendHalf::into_sink`]
+///
+/// Each item is a value and the address to send it to. Values are encoded when they are
+/// submitted, and at most one is in flight at a time.
+pub struct UdpSink<T, C = BincodeCodec> {
+    codec: C,
+    outbound: Outbound,
+    sending: Option<SendFuture>,
+    _value: PhantomData<fn(T)>,
+}
+
+impl<C: Codec + Clone> UdpSocket<C> {
+    /// Turn the socket into a [`Stream`] of values of type `T`
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use futures_util::StreamExt;
+    /// use sockit::UdpSocket;
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    ///   let mut stream = UdpSocket::bind("127.0.0.1:0").await?.into_stream::<String>();
+    ///   while let Some(Ok((message, from))) = stream.next().await {
+    ///       println!("{from}: {message}");
+    ///   }
+    ///   Ok(())
+    /// }
+    /// ```
+    pub fn into_stream<T: DeserializeOwned>(self) -> UdpStream<T, C> {
+        self.into_split().1.into_stream()
+    }
+
+    /// Turn the socket into a [`Sink`] of values of type `T` and their destinations
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use futures_util::SinkExt;
+    /// use sockit::UdpSocket;
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    ///   let mut sink = UdpSocket::bind("127.0.0.1:0").await?.into_sink::<String>();
+    ///   sink.send(("Hello World!".to_string(), "127.0.0.1:9090".parse()?)).await?;
+    ///   Ok(())
+    /// }
+    /// ```
+    pub fn into_sink<T: Serialize>(self) -> UdpSink<T, C> {
+        self.into_split().0.into_sink()
+    }
+}
+
+impl<C: Codec> RecvHalf<C> {
+    /// Turn the receiving half into a [`Stream`] of values of type `T`
+    pub fn into_stream<T: DeserializeOwned>(self) -> UdpStream<T, C> {
+        let (codec, inbound) = self.into_parts();
+        UdpStream {
+            codec,
+            state: RecvState::Idle(Box::new(inbound)),
+            _value: PhantomData,
+        }
+    }
+}
+
+impl<C: Codec> SendHalf<C> {
+    /// Turn the sending half into a [`Sink`] of values of type `T` and their destinations
+    pub fn into_sink<T: Serialize>(self) -> UdpSink<T, C> {
+        let (codec, outbound) = self.into_parts();
+        UdpSink {
+            codec,
+            outbound,
+            sending: None,
+            _value: PhantomData,
+        }
+    }
+}
+
+impl<T: DeserializeOwned, C: Codec + Unpin> Stream for UdpStream<T, C> {
+    type Item = Result<(T, SocketAddr), UdpSocketError>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        loop {
+            match std::mem::replace(&mut this.state, RecvState::Empty) {
+                RecvState::Idle(mut inbound) => {
+                    this.state = RecvState::Receiving(Box::pin(async move {
+                        let received = inbound.recv_value_frame().await;
+                        (inbound, received)
+                    }));
+                }
+                RecvState::Receiving(mut future) => match future.as_mut().poll(cx) {
+                    Poll::Ready((inbound, received)) => {
+                        let item = received.and_then(|(frame, src)| {
+                            let value = inbound.decode_value(&this.codec, &frame, src)?;
+                            Ok((value, src))
+                        });
+                        this.state = RecvState::Idle(inbound);
+                        return Poll::Ready(Some(item));
+                    }
+                    Poll::Pending => {
+                        this.state = RecvState::Receiving(future);
+                        return Poll::Pending;
+                    }
+                },
+                RecvState::Empty => unreachable!("the stream's state is always restored"),
+            }
+        }
+    }
+}
+
+impl<T: Serialize, C: Codec + Unpin> Sink<(T, SocketAddr)> for UdpSink<T, C> {
+    type Error = UdpSocketError;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.poll_flush(cx)
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: (T, SocketAddr)) -> Result<(), Self::Error> {
+        let this = self.get_mut();
+        let (value, send_to) = item;
+        let frame = this.outbound.value_frame(&this.codec, &value, send_to)?;
+
+        let outbound = this.outbound.clone();
+        this.sending = Some(Box::pin(async move {
+            outbound.send_frame(&frame, send_to).await
+        }));
+        Ok(())
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        let this = self.get_mut();
+        if let Some(sending) = this.sending.as_mut() {
+            let result = ready!(sending.as_mut().poll(cx));
+            this.sending = None;
+            result?;
+        }
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.poll_flush(cx)
+    }
+}
@@ -0,0 +1,183 @@
+use crate::max_size::FitsDatagram;
+use crate::{BincodeCodec, Codec, MaxEncodedSize, UdpSocket, UdpSocketError, DEFAULT_BUFFER_SIZE};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::marker::PhantomData;
+use std::net::SocketAddr;
+use tokio::net::ToSocketAddrs;
+
+/// A [`UdpSocket`] that only reads values of type `In` and only writes values of type `Out`
+///
+/// Fixing the message types at construction turns protocol mismatches between the two ends
+/// of a conversation into compile errors.
+///
+/// # Example
+///
+/// ```no_run
+/// use serde::{Deserialize, Serialize};
+/// use sockit::{TypedUdpSocket, UdpSocket};
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct Ping(u32);
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct Pong(u32);
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+///   let mut socket: TypedUdpSocket<Ping, Pong> =
+///       UdpSocket::bind("127.0.0.1:0").await?.into_typed();
+///   let (Ping(n), from) = socket.read().await?;
+///   socket.write(&Pong(n), from).await?;
+///   Ok(())
+/// }
+/// ```
+pub struct TypedUdpSocket<In, Out, C = BincodeCodec> {
+    socket: UdpSocket<C>,
+    _in: PhantomData<fn() -> In>,
+    _out: PhantomData<fn(&Out)>,
+}
+
+impl<C: Codec> UdpSocket<C> {
+    /// Lock the socket to reading values of type `In` and writing values of type `Out`
+    pub fn into_typed<In, Out>(self) -> TypedUdpSocket<In, Out, C>
+    where
+        In: DeserializeOwned,
+        Out: Serialize,
+    {
+        TypedUdpSocket::new(self)
+    }
+}
+
+impl<In, Out> TypedUdpSocket<In, Out>
+where
+    In: DeserializeOwned + MaxEncodedSize,
+    Out: Serialize,
+{
+    /// Bind a socket whose receive buffer holds exactly the largest value of type `In`
+    ///
+    /// The capacity leaves no room for the bytes added by sequencing, envelopes, encryption or
+    /// the other optional features, so create the [`UdpSocket`] with a larger
+    /// [capacity](UdpSocket::with_capacity) when enabling them. Types that may not fit into a
+    /// datagram are rejected at compile time. The socket uses [`BincodeCodec`], whose sizes
+    /// [`MaxEncodedSize`] counts.
+    ///
+    /// The [peer capacity](UdpSocket::peer_capacity) is left at [`DEFAULT_BUFFER_SIZE`], that of
+    /// a peer created with [`UdpSocket::bind`]. When the peer is bound with this method too, use
+    /// [`with_exact_peer_capacity`](Self::with_exact_peer_capacity) to match its buffer.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use sockit::TypedUdpSocket;
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    ///   let mut socket = TypedUdpSocket::<(u32, f64), u32>::bind("127.0.0.1:0").await?;
+    ///   let ((id, reading), from) = socket.read().await?;
+    ///   socket.write(&id, from).await?;
+    ///   Ok(())
+    /// }
+    /// ```
+    pub async fn bind<A: ToSocketAddrs>(addr: A) -> Result<Self, UdpSocketError> {
+        let () = FitsDatagram::<In>::CHECKED;
+        let socket = tokio::net::UdpSocket::bind(addr).await?;
+        let capacity = In::MAX_ENCODED_SIZE.max(1);
+        let mut socket = UdpSocket::with_capacity(socket, capacity);
+        socket.set_peer_capacity(DEFAULT_BUFFER_SIZE);
+        Ok(Self::new(socket))
+    }
+}
+
+impl<In, Out> TypedUdpSocket<In, Out>
+where
+    In: DeserializeOwned,
+    Out: Serialize + MaxEncodedSize,
+{
+    /// Set the [peer capacity](UdpSocket::peer_capacity) to exactly the largest value of type
+    /// `Out`, which is the capacity of a peer reading `Out` that was created with
+    /// [`bind`](TypedUdpSocket::bind)
+    ///
+    /// Like `bind`, this is only available with [`BincodeCodec`], whose sizes
+    /// [`MaxEncodedSize`] counts, and types that may not fit into a datagram are rejected at
+    /// compile time.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use sockit::{BoundedVec, TypedUdpSocket};
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    ///   let mut socket = TypedUdpSocket::<u32, BoundedVec<u64, 1024>>::bind("127.0.0.1:0")
+    ///       .await?
+    ///       .with_exact_peer_capacity();
+    ///   let (id, from) = socket.read().await?;
+    ///   let readings = vec![u64::from(id); 1024].try_into().unwrap();
+    ///   socket.write(&readings, from).await?;
+    ///   Ok(())
+    /// }
+    /// ```
+    pub fn with_exact_peer_capacity(mut self) -> Self {
+        let () = FitsDatagram::<Out>::CHECKED;
+        self.socket.set_peer_capacity(Out::MAX_ENCODED_SIZE.max(1));
+        self
+    }
+}
+
+impl<In, Out, C> TypedUdpSocket<In, Out, C>
+where
+    In: DeserializeOwned,
+    Out: Serialize,
+    C: Codec,
+{
+    /// Wrap a [`UdpSocket`], fixing the types it reads and writes
+    pub fn new(socket: UdpSocket<C>) -> Self {
+        Self {
+            socket,
+            _in: PhantomData,
+            _out: PhantomData,
+        }
+    }
+
+    /// Write a value to the socket
+    ///
+    /// See [`UdpSocket::write`].
+    pub async fn write(&mut self, value: &Out, send_to: SocketAddr) -> Result<(), UdpSocketError> {
+        self.socket.write(value, send_to).await
+    }
+
+    /// Read a value from the socket
+    ///
+    /// See [`UdpSocket::read`].
+    pub async fn read(&mut self) -> Result<(In, SocketAddr), UdpSocketError> {
+        self.socket.read::<In>().await
+    }
+
+    /// Get the local address of the socket
+    pub fn local_addr(&self) -> Result<SocketAddr, UdpSocketError> {
+        self.socket.local_addr()
+    }
+
+    /// Get a reference to the underlying [`UdpSocket`]
+    pub fn get_ref(&self) -> &UdpSocket<C> {
+        &self.socket
+    }
+
+    /// Get a mutable reference to the underlying [`UdpSocket`], for example to change its
+    /// configuration
+    pub fn get_mut(&mut self) -> &mut UdpSocket<C> {
+        &mut self.socket
+    }
+
+    /// Unwrap the underlying untyped [`UdpSocket`]
+    pub fn into_inner(self) -> UdpSocket<C> {
+        self.socket
+    }
+}
+
+impl<In, Out, C> From<TypedUdpSocket<In, Out, C>> for UdpSocket<C> {
+    fn from(socket: TypedUdpSocket<In, Out, C>) -> Self {
+        socket.socket
+    }
+}
@@ -2,10 +2,19 @@ extern crate sockit;
 
 #[cfg(test)]
 mod tests {
+    use futures_util::{SinkExt, StreamExt};
+    use serde::de::DeserializeOwned;
     use serde::{Deserialize, Serialize};
-    use sockit::{UdpSocket, UdpSocketError};
+    use sockit::{
+        BoundedString, BoundedVec, CancellationToken, Checksum, Codec, Dispatcher, Encryption,
+        Envelope, Fragmentation, MaxEncodedSize, MulticastInterface, Noise, NoiseKeypair, Registry,
+        RejectPolicy, Reliability, ReliableUdpSocket, RpcClient, Sequencing, Server, Signing,
+        TypedUdpSocket, UdpSocket, UdpSocketError,
+    };
+    use std::net::SocketAddr;
+    use std::time::Duration;
 
-    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
     struct TestMessage {
         id: u32,
         name: String,
@@ -38,4 +47,1420 @@ mod tests {
         assert_eq!(message, parsed_message);
         Ok(())
     }
+
+    /// A codec that stores values as bincode with every byte inverted
+    struct InvertingCodec;
+
+    impl Codec for InvertingCodec {
+        fn encode<T: Serialize>(&self, value: &T, buf: &mut Vec<u8>) -> Result<(), UdpSocketError> {
+            let encoded = bincode::serialize(value)?;
+            buf.extend(encoded.iter().map(|b| !b));
+            Ok(())
+        }
+
+        fn decode<T: DeserializeOwned>(&self, bytes: &[u8]) -> Result<T, UdpSocketError> {
+            let decoded: Vec<u8> = bytes.iter().map(|b| !b).collect();
+            Ok(bincode::deserialize(&decoded)?)
+        }
+    }
+
+    #[tokio::test]
+    async fn write_and_read_with_custom_codec() -> Result<(), UdpSocketError> {
+        let (a, b) = setup().await;
+        let (mut a, mut b) = (a.with_codec(InvertingCodec), b.with_codec(InvertingCodec));
+
+        a.write(&"Hello, world!".to_string(), b.local_addr()?)
+            .await?;
+        let (parsed_message, from) = b.read::<String>().await?;
+
+        assert_eq!(from, a.local_addr()?);
+        assert_eq!(parsed_message, "Hello, world!");
+        Ok(())
+    }
+
+    #[cfg(feature = "json")]
+    #[tokio::test]
+    async fn write_and_read_json_message() -> Result<(), UdpSocketError> {
+        let (a, b) = setup().await;
+        let (mut a, mut b) = (
+            a.with_codec(sockit::JsonCodec),
+            b.with_codec(sockit::JsonCodec),
+        );
+
+        let message = TestMessage {
+            id: 7,
+            name: "Json Message".to_string(),
+            payload: vec![9, 8, 7],
+        };
+
+        a.write(&message, b.local_addr()?).await?;
+        let (parsed_message, _) = b.read::<TestMessage>().await?;
+
+        assert_eq!(message, parsed_message);
+        Ok(())
+    }
+
+    #[cfg(feature = "json")]
+    #[tokio::test]
+    async fn declared_message_sizes_only_limit_bincode() -> Result<(), UdpSocketError> {
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        struct Ping(u32);
+
+        impl sockit::Message for Ping {
+            const ID: u32 = 1;
+            const MAX_SIZE: Option<usize> = Some(4);
+        }
+
+        let (a, b) = setup().await;
+        let (mut a, mut b) = (
+            a.with_codec(sockit::JsonCodec),
+            b.with_codec(sockit::JsonCodec),
+        );
+        for socket in [&mut a, &mut b] {
+            socket.set_registry(Registry::new().register_message::<Ping>());
+        }
+
+        // Seven bytes of JSON, but only four of bincode
+        a.write(&Ping(1234567), b.local_addr()?).await?;
+        assert_eq!(b.read::<Ping>().await?.0, Ping(1234567));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn write_and_read_message_larger_than_default_buffer() -> Result<(), UdpSocketError> {
+        let mut a = UdpSocket::bind("127.0.0.1:0").await?;
+        let b = tokio::net::UdpSocket::bind("127.0.0.1:0").await?;
+        let mut b = UdpSocket::with_capacity(b, 4096);
+        a.set_peer_capacity(b.capacity());
+
+        let message = TestMessage {
+            id: 42,
+            name: "Large Message".to_string(),
+            payload: vec![7; 2048],
+        };
+
+        a.write(&message, b.local_addr()?).await?;
+        let (parsed_message, _) = b.read::<TestMessage>().await?;
+
+        assert_eq!(b.capacity(), 4096);
+        assert_eq!(message, parsed_message);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn read_reports_truncated_datagram() -> Result<(), UdpSocketError> {
+        let a = tokio::net::UdpSocket::bind("127.0.0.1:0").await?;
+        let mut b = UdpSocket::bind("127.0.0.1:0").await?;
+
+        let message = TestMessage {
+            id: 1,
+            name: "Oversized Message".to_string(),
+            payload: vec![0; 1024],
+        };
+
+        a.send_to(&bincode::serialize(&message)?, b.local_addr()?)
+            .await?;
+
+        match b.read::<TestMessage>().await {
+            Err(UdpSocketError::Truncated { capacity, from, .. }) => {
+                assert_eq!(capacity, b.capacity());
+                assert_eq!(from, a.local_addr()?);
+            }
+            other => panic!("expected a truncated datagram, got {other:?}"),
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn write_rejects_message_larger_than_send_limit() -> Result<(), UdpSocketError> {
+        let (mut a, b) = setup().await;
+        a.set_max_datagram_size(256);
+
+        let message = TestMessage {
+            id: 1,
+            name: "Oversized Message".to_string(),
+            payload: vec![0; 300],
+        };
+
+        match a.write(&message, b.local_addr()?).await {
+            Err(UdpSocketError::MessageTooLarge { size, limit }) => {
+                assert!(size > limit);
+                assert_eq!(limit, 256);
+            }
+            other => panic!("expected the message to be rejected, got {other:?}"),
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn write_and_read_fragmented_message() -> Result<(), UdpSocketError> {
+        let (mut a, mut b) = setup().await;
+        a.set_fragmentation(Fragmentation::new());
+        b.set_fragmentation(Fragmentation::new());
+
+        let message = TestMessage {
+            id: 5,
+            name: "Fragmented Message".to_string(),
+            payload: (0..5000).map(|i| i as u8).collect(),
+        };
+
+        a.write(&message, b.local_addr()?).await?;
+        let (parsed_message, from) = b.read::<TestMessage>().await?;
+
+        assert_eq!(from, a.local_addr()?);
+        assert_eq!(message, parsed_message);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn reassembly_evicts_messages_beyond_the_pending_cap() -> Result<(), UdpSocketError> {
+        let mut b = UdpSocket::bind("127.0.0.1:0").await?;
+        b.set_fragmentation(Fragmentation::new().max_pending_messages(1));
+        let a = tokio::net::UdpSocket::bind("127.0.0.1:0").await?;
+
+        // Each value is split into two fragments: `message id | index | count | half a u32`
+        let fragment = |message_id: u32, index: u16, value: u32| {
+            let mut datagram = message_id.to_be_bytes().to_vec();
+            datagram.extend_from_slice(&index.to_be_bytes());
+            datagram.extend_from_slice(&2u16.to_be_bytes());
+            let half = index as usize * 2;
+            datagram.extend_from_slice(&value.to_le_bytes()[half..half + 2]);
+            datagram
+        };
+        let b_addr = b.local_addr()?;
+        a.send_to(&fragment(1, 0, 10), b_addr).await?;
+        // The second message evicts the first, so its last fragment starts it over
+        a.send_to(&fragment(2, 0, 20), b_addr).await?;
+        a.send_to(&fragment(2, 1, 20), b_addr).await?;
+        a.send_to(&fragment(1, 1, 10), b_addr).await?;
+        a.send_to(&fragment(3, 0, 30), b_addr).await?;
+        a.send_to(&fragment(3, 1, 30), b_addr).await?;
+
+        assert_eq!(b.read::<u32>().await?.0, 20);
+        assert_eq!(b.read::<u32>().await?.0, 30);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn write_reliable_is_acknowledged_and_read_once() -> Result<(), UdpSocketError> {
+        let (a, b) = setup().await;
+        let (mut a, mut b) = (ReliableUdpSocket::new(a), ReliableUdpSocket::new(b));
+        let (a_addr, b_addr) = (a.local_addr()?, b.local_addr()?);
+
+        let reader = tokio::spawn(async move {
+            let first = b.read::<String>().await;
+            (b, first)
+        });
+        a.write_reliable(&"first".to_string(), b_addr).await?;
+
+        let (mut b, first) = reader.await.unwrap();
+        assert_eq!(first?, ("first".to_string(), a_addr));
+
+        let reader = tokio::spawn(async move { b.read::<String>().await });
+        a.write_reliable(&"second".to_string(), b_addr).await?;
+        assert_eq!(reader.await.unwrap()?, ("second".to_string(), a_addr));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn write_reliable_ignores_stray_datagrams() -> Result<(), UdpSocketError> {
+        let (a, b) = setup().await;
+        let (mut a, mut b) = (ReliableUdpSocket::new(a), ReliableUdpSocket::new(b));
+        let b_addr = b.local_addr()?;
+
+        // A malformed datagram from a third party is waiting when the acknowledgement is awaited
+        let stray = tokio::net::UdpSocket::bind("127.0.0.1:0").await?;
+        stray.send_to(&[0xff], a.local_addr()?).await?;
+
+        let reader = tokio::spawn(async move { b.read::<String>().await });
+        a.write_reliable(&"through".to_string(), b_addr).await?;
+        assert_eq!(reader.await.unwrap()?.0, "through");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn reliable_read_leaves_values_unacknowledged_while_the_inbox_is_full(
+    ) -> Result<(), UdpSocketError> {
+        let (a, b) = setup().await;
+        let (mut a, mut b) = (ReliableUdpSocket::new(a), ReliableUdpSocket::new(b));
+        let quick = Reliability::new().initial_rto(Duration::from_millis(10));
+        a.set_reliability(quick.max_attempts(30));
+        b.set_reliability(quick.max_attempts(4).max_inbox(1));
+        let b_addr = b.local_addr()?;
+
+        let writer = tokio::spawn(async move {
+            a.write_reliable(&1u32, b_addr).await?;
+            a.write_reliable(&2u32, b_addr).await
+        });
+
+        // Both values arrive while `b` waits for an acknowledgement that never comes, but only
+        // the first fits into its inbox
+        let silent = tokio::net::UdpSocket::bind("127.0.0.1:0").await?;
+        let unanswered = b.write_reliable(&0u32, silent.local_addr()?).await;
+        assert!(matches!(
+            unanswered,
+            Err(UdpSocketError::Unacknowledged { .. })
+        ));
+        assert_eq!(b.read::<u32>().await?.0, 1);
+        assert_eq!(b.read::<u32>().await?.0, 2);
+        writer.await.unwrap()?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn write_reliable_gives_up_without_acknowledgement() -> Result<(), UdpSocketError> {
+        let (a, b) = setup().await;
+        let mut a = ReliableUdpSocket::new(a);
+        a.set_reliability(
+            Reliability::new()
+                .initial_rto(Duration::from_millis(10))
+                .max_attempts(3),
+        );
+
+        match a.write_reliable(&"unanswered", b.local_addr()?).await {
+            Err(UdpSocketError::Unacknowledged { to, attempts }) => {
+                assert_eq!(to, b.local_addr()?);
+                assert_eq!(attempts, 3);
+            }
+            other => panic!("expected the message to go unacknowledged, got {other:?}"),
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn sequenced_read_discards_stale_values() -> Result<(), UdpSocketError> {
+        let (mut a, mut b) = setup().await;
+        a.set_sequencing(Sequencing::Latest);
+        b.set_sequencing(Sequencing::Latest);
+        let b_addr = b.local_addr()?;
+
+        // Capture the datagrams of two values and deliver them in reverse order
+        let relay = tokio::net::UdpSocket::bind("127.0.0.1:0").await?;
+        a.write(&1u32, relay.local_addr()?).await?;
+        a.write(&2u32, relay.local_addr()?).await?;
+        let mut first = [0; 64];
+        let mut second = [0; 64];
+        let (first_len, _) = relay.recv_from(&mut first).await?;
+        let (second_len, _) = relay.recv_from(&mut second).await?;
+
+        relay.send_to(&second[..second_len], b_addr).await?;
+        relay.send_to(&first[..first_len], b_addr).await?;
+        relay.send_to(&second[..second_len], b_addr).await?;
+        a.write(&3u32, relay.local_addr()?).await?;
+        let (third_len, _) = relay.recv_from(&mut first).await?;
+        relay.send_to(&first[..third_len], b_addr).await?;
+
+        assert_eq!(b.read::<u32>().await?.0, 2);
+        assert_eq!(b.read::<u32>().await?.0, 3);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn ordered_read_buffers_early_values() -> Result<(), UdpSocketError> {
+        let (mut a, mut b) = setup().await;
+        let ordered = Sequencing::Ordered { max_buffered: 16 };
+        a.set_sequencing(ordered);
+        b.set_sequencing(ordered);
+        let b_addr = b.local_addr()?;
+
+        // Deliver the values in reverse order, so the first one seen isn't the first written
+        let relay = tokio::net::UdpSocket::bind("127.0.0.1:0").await?;
+        let mut datagrams = Vec::new();
+        for value in 0u32..3 {
+            a.write(&value, relay.local_addr()?).await?;
+            let mut buf = [0; 64];
+            let (len, _) = relay.recv_from(&mut buf).await?;
+            datagrams.push(buf[..len].to_vec());
+        }
+        for index in [2, 1, 0] {
+            relay.send_to(&datagrams[index], b_addr).await?;
+        }
+
+        assert_eq!(b.read::<u32>().await?.0, 0);
+        assert_eq!(b.read::<u32>().await?.0, 1);
+        assert_eq!(b.read::<u32>().await?.0, 2);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn sequenced_read_discards_values_from_older_sessions() -> Result<(), UdpSocketError> {
+        let (mut old, mut new) = setup().await;
+        let mut b = UdpSocket::bind("127.0.0.1:0").await?;
+        for socket in [&mut old, &mut new, &mut b] {
+            socket.set_sequencing(Sequencing::Latest);
+        }
+
+        // Relayed, the values of both sockets look like those of one peer that restarted
+        let relay = tokio::net::UdpSocket::bind("127.0.0.1:0").await?;
+        old.write(&1u32, relay.local_addr()?).await?;
+        new.write(&2u32, relay.local_addr()?).await?;
+        new.write(&3u32, relay.local_addr()?).await?;
+        let mut datagrams = Vec::new();
+        for _ in 0..3 {
+            let mut buf = [0; 64];
+            let (len, _) = relay.recv_from(&mut buf).await?;
+            datagrams.push(buf[..len].to_vec());
+        }
+        for datagram in [&datagrams[1], &datagrams[0], &datagrams[2]] {
+            relay.send_to(datagram, b.local_addr()?).await?;
+        }
+
+        assert_eq!(b.read::<u32>().await?.0, 2);
+        assert_eq!(b.read::<u32>().await?.0, 3);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn sequenced_read_recovers_from_spoofed_headers() -> Result<(), UdpSocketError> {
+        let (mut a, mut b) = setup().await;
+        a.set_sequencing(Sequencing::Latest);
+        b.set_sequencing(Sequencing::Latest);
+        let b_addr = b.local_addr()?;
+
+        // The relay stands in for `a`, so its forged headers look like they come from the peer
+        let relay = tokio::net::UdpSocket::bind("127.0.0.1:0").await?;
+        let forged = |sequence: u64, value: u32| {
+            let mut datagram = u64::MAX.to_be_bytes().to_vec();
+            datagram.extend_from_slice(&sequence.to_be_bytes());
+            datagram.extend_from_slice(&value.to_le_bytes());
+            datagram
+        };
+        relay.send_to(&forged(u64::MAX, 0), b_addr).await?;
+        assert!(matches!(
+            b.read::<u32>().await,
+            Err(UdpSocketError::MalformedFrame { .. })
+        ));
+        relay.send_to(&forged(0, 1), b_addr).await?;
+        assert_eq!(b.read::<u32>().await?.0, 1);
+
+        // Once the forged session goes quiet, values from the real one are delivered again
+        tokio::time::sleep(Duration::from_millis(5100)).await;
+        a.write(&2u32, relay.local_addr()?).await?;
+        let mut buf = [0; 64];
+        let (len, _) = relay.recv_from(&mut buf).await?;
+        relay.send_to(&buf[..len], b_addr).await?;
+        assert_eq!(b.read::<u32>().await?.0, 2);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn concurrent_rpc_calls_receive_matching_responses() -> Result<(), UdpSocketError> {
+        let (a, mut b) = setup().await;
+        let server_addr = b.local_addr()?;
+        tokio::spawn(async move {
+            b.serve(|(x, y): (u32, u32), _from: SocketAddr| async move { x * y })
+                .await
+        });
+
+        let client = RpcClient::new(a);
+        let timeout = Duration::from_secs(1);
+        let (six, twenty) = tokio::join!(
+            client.call::<_, u32>(server_addr, &(2u32, 3u32), timeout),
+            client.call::<_, u32>(server_addr, &(4u32, 5u32), timeout),
+        );
+
+        assert_eq!(six?, 6);
+        assert_eq!(twenty?, 20);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn rpc_call_times_out_without_server() -> Result<(), UdpSocketError> {
+        let (a, b) = setup().await;
+        let client = RpcClient::new(a);
+
+        let result = client
+            .call::<_, u32>(b.local_addr()?, &1u32, Duration::from_millis(50))
+            .await;
+
+        assert!(matches!(result, Err(UdpSocketError::RpcTimeout { to }) if to == b.local_addr()?));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn rpc_responses_from_other_addresses_are_ignored() -> Result<(), UdpSocketError> {
+        let (a, _) = setup().await;
+        let client = RpcClient::new(a);
+        let client_addr = client.local_addr();
+        let server = tokio::net::UdpSocket::bind("127.0.0.1:0").await?;
+        let impostor = tokio::net::UdpSocket::bind("127.0.0.1:0").await?;
+        let server_addr = server.local_addr()?;
+
+        let responder = tokio::spawn(async move {
+            let mut request = [0; 64];
+            server.recv_from(&mut request).await?;
+            // Answer with the request's kind and id replaced by a response carrying `value`
+            let response = |value: u32| {
+                let mut response = request[..9].to_vec();
+                response[0] = 1;
+                response.extend_from_slice(&value.to_le_bytes());
+                response
+            };
+            impostor.send_to(&response(666), client_addr).await?;
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            server.send_to(&response(7), client_addr).await
+        });
+
+        let response = client
+            .call::<_, u32>(server_addr, &1u32, Duration::from_secs(1))
+            .await?;
+        assert_eq!(response, 7);
+        responder.await.unwrap()?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn split_halves_send_and_receive_concurrently() -> Result<(), UdpSocketError> {
+        let (a, b) = setup().await;
+        let (a_send, _a_recv) = a.into_split();
+        let (_b_send, mut b_recv) = b.into_split();
+        let b_addr = b_recv.local_addr()?;
+
+        let reader = tokio::spawn(async move {
+            let mut values = Vec::new();
+            for _ in 0..4 {
+                values.push(b_recv.read::<u32>().await?.0);
+            }
+            Ok::<_, UdpSocketError>(values)
+        });
+
+        let writers: Vec<_> = (0..4u32)
+            .map(|value| {
+                let send = a_send.clone();
+                tokio::spawn(async move { send.write(&value, b_addr).await })
+            })
+            .collect();
+        for writer in writers {
+            writer.await.unwrap()?;
+        }
+
+        let mut values = reader.await.unwrap()?;
+        values.sort();
+        assert_eq!(values, vec![0, 1, 2, 3]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn stream_and_sink_carry_typed_values() -> Result<(), UdpSocketError> {
+        let (a, b) = setup().await;
+        let (a_addr, b_addr) = (a.local_addr()?, b.local_addr()?);
+        let mut sink = a.into_sink::<TestMessage>();
+        let stream = b.into_stream::<TestMessage>();
+
+        let messages: Vec<_> = (0..3)
+            .map(|id| TestMessage {
+                id,
+                name: format!("Message {id}"),
+                payload: vec![id as u8; 3],
+            })
+            .collect();
+        for message in &messages {
+            sink.feed((message.clone(), b_addr)).await?;
+        }
+        sink.flush().await?;
+
+        let received: Vec<_> = stream.take(3).collect().await;
+        for (message, item) in messages.iter().zip(received) {
+            let (parsed_message, from) = item?;
+            assert_eq!(from, a_addr);
+            assert_eq!(message, &parsed_message);
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn typed_sockets_exchange_fixed_message_types() -> Result<(), UdpSocketError> {
+        let (a, b) = setup().await;
+        let mut client: TypedUdpSocket<u64, TestMessage> = a.into_typed();
+        let mut server: TypedUdpSocket<TestMessage, u64> = b.into_typed();
+
+        let message = TestMessage {
+            id: 11,
+            name: "Typed Message".to_string(),
+            payload: vec![1, 1],
+        };
+
+        client.write(&message, server.local_addr()?).await?;
+        let (parsed_message, from) = server.read().await?;
+        server.write(&(parsed_message.id as u64), from).await?;
+        let (reply, _) = client.read().await?;
+
+        assert_eq!(message, parsed_message);
+        assert_eq!(reply, 11);
+
+        let untyped: UdpSocket = client.into_inner();
+        assert_eq!(untyped.local_addr()?, from);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn multicast_values_are_received_by_group_members() -> Result<(), UdpSocketError> {
+        let group = "239.255.77.1".parse().unwrap();
+        let mut member = UdpSocket::bind("0.0.0.0:0").await?;
+        member.join_multicast(group, MulticastInterface::V4("127.0.0.1".parse().unwrap()))?;
+
+        let mut sender = UdpSocket::bind("127.0.0.1:0").await?;
+        sender.set_multicast_loop(true)?;
+        sender.set_multicast_ttl(1)?;
+        assert_eq!(sender.multicast_ttl()?, 1);
+
+        let group_addr = SocketAddr::new(group, member.local_addr()?.port());
+        sender
+            .write_multicast(&"Hello, group!".to_string(), group_addr)
+            .await?;
+        let (message, from) = member.read::<String>().await?;
+
+        assert_eq!(message, "Hello, group!");
+        assert_eq!(from, sender.local_addr()?);
+        member.leave_multicast(group, MulticastInterface::V4("127.0.0.1".parse().unwrap()))?;
+
+        let not_a_group = sender.write_multicast(&1u8, member.local_addr()?).await;
+        assert!(matches!(
+            not_a_group,
+            Err(UdpSocketError::NotMulticast { .. })
+        ));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn write_broadcast_requires_broadcast_to_be_enabled() -> Result<(), UdpSocketError> {
+        let mut socket = UdpSocket::bind("0.0.0.0:0").await?;
+        assert!(!socket.broadcast()?);
+
+        match socket.write_broadcast(&"Hello, everyone!", 9).await {
+            Err(UdpSocketError::BroadcastDisabled { addr }) => {
+                assert_eq!(addr, "255.255.255.255:9".parse().unwrap());
+            }
+            other => panic!("expected broadcast to be refused, got {other:?}"),
+        }
+
+        socket.set_broadcast(true)?;
+        assert!(socket.broadcast()?);
+        Ok(())
+    }
+
+    #[cfg(feature = "lz4")]
+    #[tokio::test]
+    async fn compressed_message_fits_into_small_buffer() -> Result<(), UdpSocketError> {
+        let (mut a, mut b) = setup().await;
+        a.set_compression(sockit::Compression::lz4());
+        b.set_compression(sockit::Compression::lz4());
+
+        // Far larger than the 512 byte buffer, but highly repetitive
+        let message = TestMessage {
+            id: 14,
+            name: "Compressed Message".to_string(),
+            payload: vec![42; 4096],
+        };
+
+        a.write(&message, b.local_addr()?).await?;
+        let (parsed_message, _) = b.read::<TestMessage>().await?;
+
+        assert_eq!(message, parsed_message);
+        Ok(())
+    }
+
+    #[cfg(feature = "zstd")]
+    #[tokio::test]
+    async fn oversized_decompression_is_rejected() -> Result<(), UdpSocketError> {
+        let (mut a, mut b) = setup().await;
+        a.set_compression(sockit::Compression::zstd(3));
+        b.set_compression(sockit::Compression::zstd(3).max_decompressed_size(1024));
+
+        a.write(&vec![0u8; 8192], b.local_addr()?).await?;
+
+        match b.read::<Vec<u8>>().await {
+            Err(UdpSocketError::DecompressionLimitExceeded { limit, .. }) => {
+                assert_eq!(limit, 1024)
+            }
+            other => panic!("expected the message to be rejected, got {other:?}"),
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn encrypted_messages_authenticate() -> Result<(), UdpSocketError> {
+        let (mut a, mut b) = setup().await;
+        let mut c = UdpSocket::bind("127.0.0.1:0").await?;
+        a.set_encryption(Encryption::new([1; 32]));
+        b.set_encryption(Encryption::new([1; 32]));
+        c.set_encryption(Encryption::new([2; 32]));
+
+        let message = TestMessage {
+            id: 15,
+            name: "Encrypted Message".to_string(),
+            payload: vec![1, 2, 3],
+        };
+
+        a.write(&message, b.local_addr()?).await?;
+        let (parsed_message, _) = b.read::<TestMessage>().await?;
+        assert_eq!(message, parsed_message);
+
+        c.write(&message, b.local_addr()?).await?;
+        match b.read::<TestMessage>().await {
+            Err(UdpSocketError::AuthenticationFailed { from }) => {
+                assert_eq!(from, c.local_addr()?)
+            }
+            other => panic!("expected the message to fail authentication, got {other:?}"),
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn forged_datagrams_can_be_dropped() -> Result<(), UdpSocketError> {
+        let (mut a, mut b) = setup().await;
+        let forger = tokio::net::UdpSocket::bind("127.0.0.1:0").await?;
+        a.set_encryption(Encryption::new([3; 32]));
+        b.set_encryption(Encryption::new([3; 32]).on_failure(RejectPolicy::Drop));
+
+        forger.send_to(&[0; 64], b.local_addr()?).await?;
+        a.write(&"authentic", b.local_addr()?).await?;
+
+        let (message, from) = b.read::<String>().await?;
+        assert_eq!(message, "authentic");
+        assert_eq!(from, a.local_addr()?);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn replayed_datagrams_are_detected() -> Result<(), UdpSocketError> {
+        let (mut a, mut b) = setup().await;
+        let relay = tokio::net::UdpSocket::bind("127.0.0.1:0").await?;
+        a.set_encryption(Encryption::new([4; 32]));
+        b.set_encryption(Encryption::new([4; 32]));
+
+        // Capture a datagram on its way to `b` and deliver it twice
+        let mut captured = [0; 512];
+        a.write(&"pay once", relay.local_addr()?).await?;
+        let (len, _) = relay.recv_from(&mut captured).await?;
+        relay.send_to(&captured[..len], b.local_addr()?).await?;
+        relay.send_to(&captured[..len], b.local_addr()?).await?;
+
+        let (message, _) = b.read::<String>().await?;
+        assert_eq!(message, "pay once");
+        match b.read::<String>().await {
+            Err(UdpSocketError::ReplayDetected { from }) => assert_eq!(from, relay.local_addr()?),
+            other => panic!("expected the replay to be detected, got {other:?}"),
+        }
+
+        // Replaying from another address doesn't get the datagram a fresh window
+        let other_relay = tokio::net::UdpSocket::bind("127.0.0.1:0").await?;
+        other_relay
+            .send_to(&captured[..len], b.local_addr()?)
+            .await?;
+        match b.read::<String>().await {
+            Err(UdpSocketError::ReplayDetected { from }) => {
+                assert_eq!(from, other_relay.local_addr()?)
+            }
+            other => panic!("expected the replay to be detected, got {other:?}"),
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn noise_handshake_establishes_and_rekeys_sessions() -> Result<(), UdpSocketError> {
+        let (mut client, mut server) = setup().await;
+        let client_keys = NoiseKeypair::generate();
+        let server_keys = NoiseKeypair::generate();
+        let server_addr = server.local_addr()?;
+
+        client.set_noise(
+            Noise::new(client_keys.clone())
+                .peer(server_addr, server_keys.public_key())
+                .rekey_after_messages(2),
+        );
+        server.set_noise(Noise::new(server_keys).trust(client_keys.public_key()));
+
+        let echo = tokio::spawn(async move {
+            for _ in 0..5 {
+                let (n, from) = server.read::<u32>().await?;
+                server.write(&(n * 10), from).await?;
+            }
+            Ok::<_, UdpSocketError>(())
+        });
+
+        for n in 0..5u32 {
+            client.write(&n, server_addr).await?;
+            let (reply, from) = client.read::<u32>().await?;
+            assert_eq!(reply, n * 10);
+            assert_eq!(from, server_addr);
+        }
+        echo.await.unwrap()?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn noise_rejects_untrusted_peers() -> Result<(), UdpSocketError> {
+        let (mut client, mut server) = setup().await;
+        let server_keys = NoiseKeypair::generate();
+
+        client.set_noise(
+            Noise::new(NoiseKeypair::generate())
+                .peer(server.local_addr()?, server_keys.public_key()),
+        );
+        server.set_noise(Noise::new(server_keys));
+
+        client.write(&"let me in", server.local_addr()?).await?;
+        match server.read::<String>().await {
+            Err(UdpSocketError::UntrustedPeer { from }) => assert_eq!(from, client.local_addr()?),
+            other => panic!("expected the handshake to be rejected, got {other:?}"),
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn noise_rejects_replayed_handshakes() -> Result<(), UdpSocketError> {
+        let (mut client, mut server) = setup().await;
+        let client_keys = NoiseKeypair::generate();
+        let server_keys = NoiseKeypair::generate();
+        let relay = tokio::net::UdpSocket::bind("127.0.0.1:0").await?;
+
+        client.set_noise(
+            Noise::new(client_keys.clone()).peer(relay.local_addr()?, server_keys.public_key()),
+        );
+        server.set_noise(Noise::new(server_keys).trust(client_keys.public_key()));
+
+        // Capture the handshake initiation and deliver it from two addresses
+        client.write(&"hello", relay.local_addr()?).await?;
+        let mut initiation = [0; 512];
+        let (len, _) = relay.recv_from(&mut initiation).await?;
+        let other_relay = tokio::net::UdpSocket::bind("127.0.0.1:0").await?;
+        relay
+            .send_to(&initiation[..len], server.local_addr()?)
+            .await?;
+        other_relay
+            .send_to(&initiation[..len], server.local_addr()?)
+            .await?;
+
+        match server.read::<String>().await {
+            Err(UdpSocketError::ReplayDetected { from }) => {
+                assert_eq!(from, other_relay.local_addr()?)
+            }
+            other => panic!("expected the handshake to be rejected, got {other:?}"),
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn read_signed_returns_verified_signer() -> Result<(), UdpSocketError> {
+        let (mut a, mut b) = setup().await;
+        let mut impostor = UdpSocket::bind("127.0.0.1:0").await?;
+        a.set_signing(Signing::new().sign_with([5; 32]));
+        impostor.set_signing(Signing::new().sign_with([6; 32]));
+
+        let public_key = a.signing().and_then(Signing::public_key).unwrap();
+        b.set_signing(Signing::new().trust(public_key));
+
+        a.write(&"signed", b.local_addr()?).await?;
+        let (message, from, signer) = b.read_signed::<String>().await?;
+        assert_eq!(message, "signed");
+        assert_eq!(from, a.local_addr()?);
+        assert_eq!(signer, public_key);
+
+        impostor.write(&"forged", b.local_addr()?).await?;
+        match b.read::<String>().await {
+            Err(UdpSocketError::UntrustedSigner { public_key, .. }) => {
+                assert_eq!(Some(public_key), impostor.signing().unwrap().public_key())
+            }
+            other => panic!("expected the signer to be rejected, got {other:?}"),
+        }
+
+        // `a` only signs, so it has nothing to verify signers with
+        match a.read_signed::<String>().await {
+            Err(UdpSocketError::VerificationDisabled) => {}
+            other => panic!("expected verification to be disabled, got {other:?}"),
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn signatures_are_verified_before_sequencing() -> Result<(), UdpSocketError> {
+        let (mut a, mut b) = setup().await;
+        a.set_signing(Signing::new().sign_with([5; 32]));
+        let public_key = a.signing().and_then(Signing::public_key).unwrap();
+        b.set_signing(
+            Signing::new()
+                .trust(public_key)
+                .on_failure(RejectPolicy::Drop),
+        );
+        a.set_sequencing(Sequencing::Latest);
+        b.set_sequencing(Sequencing::Latest);
+        let b_addr = b.local_addr()?;
+
+        // A signed value whose sequencing header was moved to a later session is dropped
+        let relay = tokio::net::UdpSocket::bind("127.0.0.1:0").await?;
+        let mut datagrams = Vec::new();
+        for value in [1u32, 2] {
+            a.write(&value, relay.local_addr()?).await?;
+            let mut buf = [0; 256];
+            let (len, _) = relay.recv_from(&mut buf).await?;
+            datagrams.push(buf[..len].to_vec());
+        }
+        datagrams[0][..8].copy_from_slice(&u64::MAX.to_be_bytes());
+        for datagram in &datagrams {
+            relay.send_to(datagram, b_addr).await?;
+        }
+
+        let (value, _, signer) = b.read_signed::<u32>().await?;
+        assert_eq!((value, signer), (2, public_key));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn reliable_and_rpc_frames_are_verified() -> Result<(), UdpSocketError> {
+        let (mut a, mut b) = setup().await;
+        let mut c = UdpSocket::bind("127.0.0.1:0").await?;
+        a.set_signing(Signing::new().sign_with([9; 32]));
+        let public_key = a.signing().and_then(Signing::public_key).unwrap();
+        b.set_signing(Signing::new().trust(public_key));
+        c.set_sequencing(Sequencing::Latest);
+        let (mut a, mut b) = (ReliableUdpSocket::new(a), ReliableUdpSocket::new(b));
+        let b_addr = b.local_addr()?;
+
+        let reader = tokio::spawn(async move {
+            let signed = b.read::<String>().await;
+            let unsigned = b.read::<String>().await;
+            (signed, unsigned)
+        });
+        a.write_reliable(&"signed".to_string(), b_addr).await?;
+
+        // An unsigned value is rejected before it's acknowledged
+        let mut impostor = ReliableUdpSocket::new(UdpSocket::bind("127.0.0.1:0").await?);
+        impostor.set_reliability(Reliability::new().max_attempts(1));
+        let impostor_addr = impostor.local_addr()?;
+        let unacknowledged = impostor
+            .write_reliable(&"unsigned".to_string(), b_addr)
+            .await;
+        assert!(matches!(
+            unacknowledged,
+            Err(UdpSocketError::Unacknowledged { .. })
+        ));
+
+        let (signed, unsigned) = reader.await.unwrap();
+        assert_eq!(signed?.0, "signed");
+        match unsigned {
+            Err(UdpSocketError::InvalidSignature { from }) => assert_eq!(from, impostor_addr),
+            other => panic!("expected the unsigned value to be rejected, got {other:?}"),
+        }
+
+        // RPC numbers its own requests, so it can't be combined with sequencing
+        let client = RpcClient::new(c);
+        match client
+            .call::<_, u32>(b_addr, &1u32, Duration::from_millis(10))
+            .await
+        {
+            Err(UdpSocketError::SequencingUnsupported) => {}
+            other => panic!("expected sequencing to be rejected, got {other:?}"),
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn corrupted_datagrams_fail_checksum() -> Result<(), UdpSocketError> {
+        let (mut a, mut b) = setup().await;
+        let relay = tokio::net::UdpSocket::bind("127.0.0.1:0").await?;
+        a.set_checksum(Checksum::Crc32);
+        b.set_checksum(Checksum::Crc32);
+
+        a.write(&42u64, b.local_addr()?).await?;
+        assert_eq!(b.read::<u64>().await?.0, 42);
+
+        // Flip a bit on the way to `b`; the value would still deserialize without the checksum
+        let mut datagram = [0; 512];
+        a.write(&42u64, relay.local_addr()?).await?;
+        let (len, _) = relay.recv_from(&mut datagram).await?;
+        datagram[0] ^= 1;
+        relay.send_to(&datagram[..len], b.local_addr()?).await?;
+
+        match b.read::<u64>().await {
+            Err(UdpSocketError::ChecksumMismatch { from }) => assert_eq!(from, relay.local_addr()?),
+            other => panic!("expected a checksum mismatch, got {other:?}"),
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn envelope_rejects_foreign_and_incompatible_values() -> Result<(), UdpSocketError> {
+        let (mut a, mut b) = setup().await;
+        let stray = tokio::net::UdpSocket::bind("127.0.0.1:0").await?;
+        a.set_envelope(Envelope::new(2));
+        b.set_envelope(Envelope::new(3).accept_versions(2..=3));
+        // The registry's tags identify the type of the value inside the envelope
+        a.set_registry(Registry::new().register::<u32>(1).register::<String>(2));
+        b.set_registry(Registry::new().register::<u32>(1).register::<String>(2));
+
+        a.write(&7u32, b.local_addr()?).await?;
+        assert_eq!(b.read::<u32>().await?.0, 7);
+
+        a.write(&7u32, b.local_addr()?).await?;
+        match b.read::<String>().await {
+            Err(UdpSocketError::UnexpectedMessageType { .. }) => {}
+            other => panic!("expected a message type mismatch, got {other:?}"),
+        }
+
+        a.set_envelope(Envelope::new(1));
+        a.write(&7u32, b.local_addr()?).await?;
+        match b.read::<u32>().await {
+            Err(UdpSocketError::ProtocolVersionMismatch { got, .. }) => assert_eq!(got, 1),
+            other => panic!("expected a protocol version mismatch, got {other:?}"),
+        }
+
+        stray.send_to(&[7, 0, 0, 0], b.local_addr()?).await?;
+        match b.read::<u32>().await {
+            Err(UdpSocketError::ForeignPacket { from }) => assert_eq!(from, stray.local_addr()?),
+            other => panic!("expected a foreign packet, got {other:?}"),
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn fingerprints_reject_values_of_other_types() -> Result<(), UdpSocketError> {
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        enum Kind {
+            Reading(f32),
+            Alarm { level: u8 },
+        }
+
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        struct Sample {
+            kind: Kind,
+            at: (u32, u16),
+            note: Option<String>,
+            tags: Vec<TestMessage>,
+        }
+
+        #[derive(Debug, Serialize, Deserialize)]
+        struct Position {
+            x: u32,
+            y: u32,
+        }
+
+        #[derive(Debug, Serialize, Deserialize)]
+        struct Counter {
+            count: u64,
+        }
+
+        let (mut a, mut b) = setup().await;
+        a.set_fingerprinting(true);
+        b.set_fingerprinting(true);
+
+        let sample = Sample {
+            kind: Kind::Alarm { level: 3 },
+            at: (7, 9),
+            note: Some("hot".to_string()),
+            tags: Vec::new(),
+        };
+        a.write(&sample, b.local_addr()?).await?;
+        assert_eq!(b.read::<Sample>().await?.0, sample);
+
+        // Both encode as 8 bytes with bincode, so only the fingerprint tells them apart
+        a.write(&Position { x: 1, y: 2 }, b.local_addr()?).await?;
+        match b.read::<Counter>().await {
+            Err(UdpSocketError::TypeMismatch { expected, got, .. }) => assert_ne!(expected, got),
+            other => panic!("expected a type mismatch, got {other:?}"),
+        }
+
+        // The types inside collections count too
+        a.write(&vec![1u32], b.local_addr()?).await?;
+        match b.read::<Vec<String>>().await {
+            Err(UdpSocketError::TypeMismatch { expected, got, .. }) => assert_ne!(expected, got),
+            other => panic!("expected a type mismatch, got {other:?}"),
+        }
+        Ok(())
+    }
+
+    #[cfg(feature = "json")]
+    #[tokio::test]
+    async fn fingerprints_follow_the_fields_a_value_holds() -> Result<(), UdpSocketError> {
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        enum Unit {
+            Celsius,
+            Kelvin { offset: i16 },
+        }
+
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        struct Reading {
+            #[serde(skip_serializing_if = "Option::is_none")]
+            unit: Option<Unit>,
+            values: Vec<f64>,
+        }
+
+        let (a, b) = setup().await;
+        let (mut a, mut b) = (
+            a.with_codec(sockit::JsonCodec),
+            b.with_codec(sockit::JsonCodec),
+        );
+        a.set_fingerprinting(true);
+        b.set_fingerprinting(true);
+
+        // Every value is traced on its own, so skipped fields and other variants still match
+        let readings = [
+            Reading {
+                unit: None,
+                values: Vec::new(),
+            },
+            Reading {
+                unit: Some(Unit::Celsius),
+                values: vec![21.5],
+            },
+            Reading {
+                unit: Some(Unit::Kelvin { offset: -3 }),
+                values: vec![1.0, 2.0],
+            },
+        ];
+        for reading in &readings {
+            a.write(reading, b.local_addr()?).await?;
+            assert_eq!(&b.read::<Reading>().await?.0, reading);
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn registered_types_are_fingerprinted_with_their_contents() -> Result<(), UdpSocketError>
+    {
+        mod v1 {
+            use serde::{Deserialize, Serialize};
+
+            #[derive(Serialize, Deserialize)]
+            pub enum Limit {
+                Fixed(u32),
+            }
+
+            #[derive(Serialize, Deserialize)]
+            pub struct Config {
+                pub limits: Vec<Limit>,
+                pub name: Option<String>,
+            }
+        }
+
+        mod v2 {
+            use serde::{Deserialize, Serialize};
+
+            #[derive(Debug, Serialize, Deserialize)]
+            pub enum Limit {
+                Fixed(u64),
+            }
+
+            #[derive(Debug, Serialize, Deserialize)]
+            pub struct Config {
+                pub limits: Vec<Limit>,
+                pub name: Option<String>,
+            }
+        }
+
+        let (mut a, mut b) = setup().await;
+        a.set_fingerprinting(true);
+        b.set_fingerprinting(true);
+        a.set_registry(Registry::new().register::<v1::Config>(1));
+        b.set_registry(Registry::new().register::<v2::Config>(1));
+
+        // Without any limits, the values would decode fine despite the types differing
+        let config = v1::Config {
+            limits: Vec::new(),
+            name: None,
+        };
+        a.write(&config, b.local_addr()?).await?;
+        match b.read::<v2::Config>().await {
+            Err(UdpSocketError::TypeMismatch { expected, got, .. }) => assert_ne!(expected, got),
+            other => panic!("expected a type mismatch, got {other:?}"),
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn registered_messages_are_read_and_dispatched_by_type() -> Result<(), UdpSocketError> {
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        struct Ping(u32);
+
+        let (mut a, mut b) = setup().await;
+        a.set_registry(
+            Registry::new()
+                .register::<Ping>(1)
+                .register::<TestMessage>(2),
+        );
+        b.set_registry(
+            Registry::new()
+                .register::<Ping>(1)
+                .register::<TestMessage>(2),
+        );
+
+        let message = TestMessage {
+            id: 9,
+            name: "status".to_string(),
+            payload: vec![1, 2, 3],
+        };
+        a.write(&Ping(4), b.local_addr()?).await?;
+        a.write(&message, b.local_addr()?).await?;
+
+        let (first, from) = b.read_any().await?;
+        assert_eq!(from, a.local_addr()?);
+        assert_eq!(first.tag(), 1);
+        assert_eq!(first.downcast::<Ping>().unwrap(), Ping(4));
+
+        let pings = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let seen = pings.clone();
+        let mut dispatcher =
+            Dispatcher::new().on(move |ping: Ping, _| seen.lock().unwrap().push(ping));
+        let (unhandled, _) = dispatcher.dispatch(&mut b).await?.unwrap();
+        assert_eq!(unhandled.downcast::<TestMessage>().unwrap(), message);
+
+        a.write(&Ping(5), b.local_addr()?).await?;
+        assert!(dispatcher.dispatch(&mut b).await?.is_none());
+        assert_eq!(*pings.lock().unwrap(), [Ping(5)]);
+
+        match a.write(&7u32, b.local_addr()?).await {
+            Err(UdpSocketError::UnregisteredMessageType { .. }) => {}
+            other => panic!("expected an unregistered message type, got {other:?}"),
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn registries_match_borrowed_values_and_are_required_by_read_any(
+    ) -> Result<(), UdpSocketError> {
+        use std::borrow::Cow;
+
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        struct Note<'a>(Cow<'a, str>);
+
+        let (mut a, mut b) = setup().await;
+        match b.read_any().await {
+            Err(UdpSocketError::NoRegistry) => {}
+            other => panic!("expected a missing registry, got {other:?}"),
+        }
+        match Server::new(b).run(CancellationToken::new()).await {
+            Err(UdpSocketError::NoRegistry) => {}
+            other => panic!("expected a missing registry, got {other:?}"),
+        }
+
+        let (_, mut b) = setup().await;
+        a.set_registry(Registry::new().register::<Note<'static>>(1));
+        b.set_registry(Registry::new().register::<Note<'static>>(1));
+        let text = "written by reference".to_string();
+        a.write(&Note(Cow::Borrowed(&text)), b.local_addr()?)
+            .await?;
+        let (message, _) = b.read_any().await?;
+        assert_eq!(message.downcast::<Note>().unwrap().0, text);
+        Ok(())
+    }
+
+    #[cfg(feature = "derive")]
+    #[tokio::test]
+    async fn derived_messages_plug_into_write_and_read() -> Result<(), UdpSocketError> {
+        use sockit::Message;
+
+        #[derive(Debug, PartialEq, Serialize, Deserialize, sockit::Message)]
+        #[message(id = 40, max_size = 16)]
+        struct Telemetry {
+            sensor: u16,
+            label: String,
+        }
+
+        #[derive(Debug, PartialEq, Serialize, Deserialize, sockit::Message)]
+        struct Heartbeat;
+
+        #[derive(Serialize, Deserialize, sockit::Message, sockit::MaxEncodedSize)]
+        struct Position(i32, i32);
+
+        #[derive(Serialize, Deserialize, sockit::Message, sockit::MaxEncodedSize)]
+        #[message(max_size = 12)]
+        struct Velocity(i32, i32);
+
+        assert_eq!(Telemetry::ID, 40);
+        assert_eq!(Telemetry::MAX_SIZE, Some(16));
+        assert_eq!(Heartbeat::MAX_SIZE, None);
+        assert_eq!(Telemetry::FINGERPRINT, None);
+        assert_eq!(Position::MAX_SIZE, Some(Position::MAX_ENCODED_SIZE));
+        assert_eq!(Velocity::MAX_SIZE, Some(12));
+
+        let (mut a, mut b) = setup().await;
+        for socket in [&mut a, &mut b] {
+            socket.set_fingerprinting(true);
+            socket.set_registry(
+                Registry::new()
+                    .register_message::<Telemetry>()
+                    .register_message::<Heartbeat>(),
+            );
+        }
+
+        let telemetry = Telemetry {
+            sensor: 3,
+            label: "temp".to_string(),
+        };
+        a.write(&telemetry, b.local_addr()?).await?;
+        assert_eq!(b.read::<Telemetry>().await?.0, telemetry);
+
+        a.write(&Heartbeat, b.local_addr()?).await?;
+        let (message, _) = b.read_any().await?;
+        assert_eq!(message.tag(), Heartbeat::ID);
+
+        let oversized = Telemetry {
+            sensor: 3,
+            label: "far too long for sixteen bytes".to_string(),
+        };
+        match a.write(&oversized, b.local_addr()?).await {
+            Err(UdpSocketError::MessageTooLarge { limit, .. }) => assert_eq!(limit, 16),
+            other => panic!("expected the message to be too large, got {other:?}"),
+        }
+
+        // Changing a field's type changes the fingerprint of every message holding it
+        mod v1 {
+            #[derive(serde::Serialize, serde::Deserialize)]
+            pub struct Reading(pub u32);
+
+            #[derive(serde::Serialize, serde::Deserialize, sockit::Message)]
+            #[message(id = 41)]
+            pub struct Report {
+                pub readings: Vec<Reading>,
+            }
+        }
+
+        mod v2 {
+            #[derive(Debug, serde::Serialize, serde::Deserialize)]
+            pub struct Reading(pub u64);
+
+            #[derive(Debug, serde::Serialize, serde::Deserialize, sockit::Message)]
+            #[message(id = 41)]
+            pub struct Report {
+                pub readings: Vec<Reading>,
+            }
+        }
+
+        a.set_registry(Registry::new().register_message::<v1::Report>());
+        b.set_registry(Registry::new().register_message::<v2::Report>());
+        let report = v1::Report {
+            readings: Vec::new(),
+        };
+        a.write(&report, b.local_addr()?).await?;
+        match b.read::<v2::Report>().await {
+            Err(UdpSocketError::TypeMismatch { expected, got, .. }) => assert_ne!(expected, got),
+            other => panic!("expected a type mismatch, got {other:?}"),
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn bounded_types_fit_their_maximum_encoded_size() -> Result<(), UdpSocketError> {
+        type Reading = (u16, BoundedString<12>, BoundedVec<u32, 4>, Option<f64>);
+        let largest: Reading = (
+            u16::MAX,
+            "twelve bytes".try_into().unwrap(),
+            vec![u32::MAX; 4].try_into().unwrap(),
+            Some(f64::MAX),
+        );
+        let mut encoded = Vec::new();
+        sockit::BincodeCodec.encode(&largest, &mut encoded)?;
+        assert_eq!(encoded.len(), Reading::MAX_ENCODED_SIZE);
+        assert!(BoundedVec::<u8, 2>::try_from(vec![1, 2, 3]).is_err());
+
+        let mut typed = TypedUdpSocket::<Reading, Reading>::bind("127.0.0.1:0").await?;
+        assert_eq!(typed.get_ref().capacity(), Reading::MAX_ENCODED_SIZE);
+        let mut a = UdpSocket::bind("127.0.0.1:0").await?;
+        a.write_bounded(&largest, typed.local_addr()?).await?;
+        assert_eq!(typed.read().await?.0, largest);
+
+        // Even a short reading is refused once the largest one might not fit
+        a.set_max_datagram_size(Reading::MAX_ENCODED_SIZE - 1);
+        let short: Reading = (1, BoundedString::new(), BoundedVec::new(), None);
+        match a.write_bounded(&short, typed.local_addr()?).await {
+            Err(UdpSocketError::MessageTooLarge { size, .. }) => {
+                assert_eq!(size, Reading::MAX_ENCODED_SIZE)
+            }
+            other => panic!("expected the type to be too large, got {other:?}"),
+        }
+
+        // Replies are bounded by the peer, not by the values this socket reads
+        let mut typed = TypedUdpSocket::<u8, String>::bind("127.0.0.1:0").await?;
+        assert_eq!(typed.get_ref().capacity(), 1);
+        assert_eq!(typed.get_ref().peer_capacity(), sockit::DEFAULT_BUFFER_SIZE);
+        let mut b = UdpSocket::bind("127.0.0.1:0").await?;
+        let reply = "longer than a single byte".to_string();
+        typed.write(&reply, b.local_addr()?).await?;
+        assert_eq!(b.read::<String>().await?.0, reply);
+
+        let typed = TypedUdpSocket::<u8, Reading>::bind("127.0.0.1:0")
+            .await?
+            .with_exact_peer_capacity();
+        assert_eq!(typed.get_ref().peer_capacity(), Reading::MAX_ENCODED_SIZE);
+        Ok(())
+    }
+
+    #[cfg(feature = "derive")]
+    #[test]
+    fn derived_max_encoded_size_adds_up_fields_and_variants() {
+        #[derive(sockit::MaxEncodedSize)]
+        #[allow(dead_code)]
+        enum Command {
+            Stop,
+            Move { x: i32, y: i32 },
+            Say(BoundedString<20>),
+        }
+
+        #[derive(sockit::MaxEncodedSize)]
+        #[allow(dead_code)]
+        struct Batch<T> {
+            id: u64,
+            commands: BoundedVec<T, 3>,
+        }
+
+        assert_eq!(Command::MAX_ENCODED_SIZE, 4 + 8 + 20);
+        assert_eq!(Batch::<Command>::MAX_ENCODED_SIZE, 8 + 8 + 3 * 32);
+    }
+
+    #[tokio::test]
+    async fn server_replies_concurrently_and_shuts_down_gracefully() -> Result<(), UdpSocketError> {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        struct Ping(u32);
+
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        struct Pong(u32);
+
+        let registry = || Registry::new().register::<Ping>(1).register::<Pong>(2);
+        let (mut client, mut socket) = setup().await;
+        client.set_registry(registry());
+        socket.set_registry(registry());
+
+        let running = Arc::new(AtomicUsize::new(0));
+        let most_running = Arc::new(AtomicUsize::new(0));
+        let (counters, most) = (running.clone(), most_running.clone());
+        let server = Server::new(socket)
+            .concurrency_limit(2)
+            .handle(move |Ping(n), _| {
+                let (running, most) = (counters.clone(), most.clone());
+                async move {
+                    most.fetch_max(running.fetch_add(1, Ordering::SeqCst) + 1, Ordering::SeqCst);
+                    tokio::time::sleep(Duration::from_millis(50)).await;
+                    running.fetch_sub(1, Ordering::SeqCst);
+                    Pong(n * 10)
+                }
+            });
+        let server_addr = server.local_addr()?;
+        let shutdown = CancellationToken::new();
+        let serving = tokio::spawn(server.run(shutdown.clone()));
+
+        for n in 0..4 {
+            client.write(&Ping(n), server_addr).await?;
+        }
+        let mut pongs = Vec::new();
+        for _ in 0..4 {
+            let (pong, from) = client.read::<Pong>().await?;
+            assert_eq!(from, server_addr);
+            pongs.push(pong.0);
+        }
+        pongs.sort();
+        assert_eq!(pongs, [0, 10, 20, 30]);
+        assert_eq!(most_running.load(Ordering::SeqCst), 2);
+
+        // The handler still running when the server is shut down gets to reply
+        client.write(&Ping(5), server_addr).await?;
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        shutdown.cancel();
+        serving.await.unwrap()?;
+        assert_eq!(running.load(Ordering::SeqCst), 0);
+        assert_eq!(client.read::<Pong>().await?.0, Pong(50));
+        Ok(())
+    }
 }